@@ -0,0 +1,69 @@
+use clap::Parser;
+use std::path::Path;
+
+use super::{csv::parse_byte, parse_format, verify_file, OutputFormat};
+use crate::{process_convert, CmdExector};
+
+#[derive(Debug, Parser)]
+pub struct ConvertOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    /// Output file, "-" for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    /// Input format (json, yaml or toml). Guessed from the file extension when omitted
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    /// Separator used to join nested keys into a column name, e.g. address.city
+    #[arg(long, default_value = ".")]
+    pub separator: String,
+
+    /// Key of the array of tables to read from a TOML document
+    #[arg(long)]
+    pub table: Option<String>,
+
+    #[arg(short, long, value_parser = parse_byte, default_value = ",")]
+    pub delimiter: u8,
+}
+
+impl CmdExector for ConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = match self.format {
+            Some(format) => format,
+            None => guess_format(&self.input)?,
+        };
+        process_convert(
+            &self.input,
+            &self.output,
+            format,
+            &self.separator,
+            self.table.as_deref(),
+            self.delimiter,
+        )
+    }
+}
+
+fn guess_format(input: &str) -> anyhow::Result<OutputFormat> {
+    match Path::new(input).extension().and_then(|ext| ext.to_str()) {
+        Some("jsonl" | "ndjson") => Ok(OutputFormat::Json),
+        Some(ext) => ext.parse(),
+        None => Ok(OutputFormat::Json),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_format() {
+        assert!(matches!(guess_format("a.yml"), Ok(OutputFormat::Yaml)));
+        assert!(matches!(guess_format("a.ndjson"), Ok(OutputFormat::Json)));
+        assert!(matches!(guess_format("-"), Ok(OutputFormat::Json)));
+        assert!(matches!(guess_format("d.v2/data"), Ok(OutputFormat::Json)));
+        assert!(guess_format("a.xml").is_err());
+    }
+}
@@ -1,33 +1,551 @@
-use crate::{process_csv, CmdExector};
+use crate::{
+    parse_filter, parse_mask, parse_rename, parse_size, parse_types, process_csv, process_csv_agg,
+    process_csv_concat, process_csv_diff, process_csv_join, process_csv_query, process_csv_show,
+    process_csv_split, process_csv_stats, process_csv_validate, process_list_sheets, Aggregate,
+    CellParser, CellRange, CmdExector, ColumnType, Filter, SortKey, SplitBy,
+};
 
-use super::verify_file;
-use clap::Parser;
+use super::{verify_file, verify_path};
+use clap::{ArgAction, ArgGroup, Args, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
+use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug, Parser, Clone, Copy)]
 pub enum OutputFormat {
     Json,
     Yaml,
-    //Toml,
+    Toml,
+    Csv,
+    Tsv,
+    Parquet,
+    Arrow,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[command(flatten)]
+    pub sheet: SheetOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub write: CsvWriteOpts,
+
+    /// Infer integers, floats, booleans, dates and nulls instead of emitting every cell as a string
+    #[arg(long)]
+    pub infer: bool,
+
+    /// Per-column type overrides, e.g. "Kit Number=int,DOB=date"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<HashMap<String, ColumnType>>,
+
+    /// strftime format for date cells that are not ISO 8601, e.g. "%b %d, %Y"
+    #[arg(long)]
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum CsvSubCommand {
+    #[command(name = "show", about = "Show CSV as a table in the terminal")]
+    Show(CsvShowOpts),
+    #[command(name = "stats", about = "Profile every column of a CSV file")]
+    Stats(CsvStatsOpts),
+    #[command(name = "validate", about = "Validate CSV against a schema file")]
+    Validate(CsvValidateOpts),
+    #[command(name = "join", about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(
+        name = "concat",
+        about = "Stack CSV files, taking the union of their headers"
+    )]
+    Concat(CsvConcatOpts),
+    #[command(name = "agg", about = "Aggregate CSV records by group")]
+    Agg(CsvAggOpts),
+    #[command(name = "query", about = "Query CSV files with SQL")]
+    Query(CsvQueryOpts),
+    #[command(name = "diff", about = "Compare two versions of a CSV file by key")]
+    Diff(CsvDiffOpts),
+    #[command(
+        name = "split",
+        about = "Split a CSV file into parts that repeat the header"
+    )]
+    Split(CsvSplitOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    /// Comma separated list of columns to show
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+
+    /// Show only the first N records
+    #[arg(long, conflicts_with = "tail")]
+    pub head: Option<usize>,
+
+    /// Show only the last N records
+    #[arg(long)]
+    pub tail: Option<usize>,
+
+    /// Truncate cells wider than this many terminal columns
+    #[arg(long, default_value_t = 40)]
+    pub max_width: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    /// Estimate distinct counts and top values with bounded memory
+    #[arg(long)]
+    pub approx: bool,
+
+    /// Number of most frequent values to report per column
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+
+    #[arg(long, value_parser = parse_stats_format, default_value = "table")]
+    pub format: StatsFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    /// Schema file in YAML, JSON or TOML
+    #[arg(short, long, value_parser = verify_file)]
+    pub schema: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
     #[arg(short, long, value_parser = verify_file)]
+    pub left: String,
+
+    #[arg(short, long, value_parser = verify_file)]
+    pub right: String,
+
+    /// Comma separated key columns, present in both files
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub on: Vec<String>,
+
+    /// inner, left or outer
+    #[arg(long, value_parser = parse_join_type, default_value = "inner")]
+    pub how: JoinType,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[command(flatten)]
+    pub write: CsvWriteOpts,
+
+    /// Infer integers, floats, booleans, dates and nulls instead of emitting every cell as a string
+    #[arg(long)]
+    pub infer: bool,
+
+    /// Per-column type overrides, e.g. "Kit Number=int,DOB=date"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<HashMap<String, ColumnType>>,
+
+    /// strftime format for date cells that are not ISO 8601, e.g. "%b %d, %Y"
+    #[arg(long)]
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvConcatOpts {
+    /// Input files, in output order
+    #[arg(value_parser = verify_file, required = true)]
+    pub inputs: Vec<String>,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[command(flatten)]
+    pub write: CsvWriteOpts,
+
+    /// Infer integers, floats, booleans, dates and nulls instead of emitting every cell as a string
+    #[arg(long)]
+    pub infer: bool,
+
+    /// Per-column type overrides, e.g. "Kit Number=int,DOB=date"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<HashMap<String, ColumnType>>,
+
+    /// strftime format for date cells that are not ISO 8601, e.g. "%b %d, %Y"
+    #[arg(long)]
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvAggOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    /// Comma separated columns to group by. Without it the whole file is one group
+    #[arg(long, value_delimiter = ',')]
+    pub group_by: Vec<String>,
+
+    /// Comma separated aggregates: count, sum, avg, min, max, first or last of a column, or count(*)
+    #[arg(long, value_delimiter = ',', value_parser = parse_aggregate, required = true)]
+    pub agg: Vec<Aggregate>,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[command(flatten)]
+    pub write: CsvWriteOpts,
+}
+
+#[derive(Debug, Parser)]
+#[command(mut_arg("format", |arg| arg.requires("output")))]
+pub struct CsvQueryOpts {
+    /// SQL statement; each input is a table named after its file, e.g. juventus for juventus.csv.
+    /// The result is printed as a table unless --output is given, which --format applies to
+    pub sql: String,
+
+    /// Input files. Can be repeated
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub input: Vec<String>,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[command(flatten)]
+    pub write: CsvWriteOpts,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("split_by").required(true).args(["rows", "size", "by_column"])))]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    /// Records per part
+    #[arg(long)]
+    pub rows: Option<usize>,
+
+    /// Maximum size of a part, e.g. 50MB or 512KiB
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<u64>,
+
+    /// Write one part per distinct value of this column
+    #[arg(long)]
+    pub by_column: Option<String>,
+
+    /// Directory the parts are written to. Existing parts are never overwritten
+    #[arg(short, long, value_parser = verify_path, default_value = ".")]
+    pub output_dir: PathBuf,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    /// Comma separated columns identifying a record in both files
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub key: Vec<String>,
+
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+
+    #[arg(long, value_parser = parse_diff_format, default_value = "text")]
+    pub format: DiffFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Outer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvQuoteStyle {
+    Always,
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Snappy,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexStyle {
+    #[default]
+    Brackets,
+    Separator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStrategy {
+    Hash,
+    Redact,
+    Fake,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputEncoding {
+    Auto,
+    Label(&'static Encoding),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct CsvReadOpts {
+    /// Field delimiter, a single ASCII character or an escape such as '\t'
+    #[arg(short, long, value_parser = parse_byte, default_value = ",")]
+    pub delimiter: u8,
+
+    #[arg(long, value_parser = parse_byte, default_value = "\"")]
+    pub quote: u8,
+
+    /// Escape character for quotes inside quoted fields, instead of doubling them
+    #[arg(long, value_parser = parse_byte)]
+    pub escape: Option<u8>,
+
+    /// Lines starting with this character are skipped
+    #[arg(long, value_parser = parse_byte)]
+    pub comment: Option<u8>,
+
+    /// Allow records with a different number of fields than the header
+    #[arg(long)]
+    pub flexible: bool,
+
+    /// Trim whitespace around headers and fields
+    #[arg(long)]
+    pub trim: bool,
+
+    /// Whether the first row is a header; without one, keys are named col_0..col_n
+    #[arg(long, default_value_t = true, action = ArgAction::Set, num_args = 0..=1, default_missing_value = "true")]
+    pub header: bool,
+
+    /// Input encoding such as utf-8, utf-16le, gbk or shift_jis; "auto" detects it from a BOM or the content
+    #[arg(long, value_parser = parse_encoding, default_value = "auto")]
+    pub encoding: InputEncoding,
+}
+
+#[derive(Debug, Args, Clone, Default)]
+pub struct SheetOpts {
+    /// Worksheet to read from an .xlsx, .xls, .xlsb or .ods input. Defaults to the first one
+    #[arg(long)]
+    pub sheet: Option<String>,
+
+    /// Cells to read from the worksheet, e.g. "A3:D40", or a top-left cell such as "B2"
+    #[arg(long, value_parser = parse_cell_range)]
+    pub range: Option<CellRange>,
+
+    /// Print the worksheet names of the input workbook and exit
+    #[arg(long)]
+    pub list_sheets: bool,
+}
+
+#[derive(Debug, Args, Clone, Default)]
+pub struct CsvTransformOpts {
+    /// Comma separated list of columns to keep, in output order
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+
+    /// Comma separated list of columns to drop
+    #[arg(long, value_delimiter = ',')]
+    pub exclude: Vec<String>,
+
+    /// Rename a column, e.g. "Kit Number=kit". Can be repeated
+    #[arg(long, value_parser = parse_rename)]
+    pub rename: Vec<(String, String)>,
+
+    /// Keep records matching an expression, e.g. 'Position == "Goalkeeper" and Name =~ "^G"'
+    #[arg(long = "where", value_parser = parse_filter)]
+    pub filter: Option<Filter>,
+
+    /// Sort by columns, e.g. "Position,Kit Number:desc"
+    #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+    pub sort_by: Vec<SortKey>,
+
+    /// Nest headers like address.city and tags[0] into objects and arrays, splitting on SEPARATOR
+    #[arg(long, value_name = "SEPARATOR", num_args = 0..=1, default_missing_value = ".")]
+    pub unflatten: Option<String>,
+
+    /// How array indices are written in headers when unflattening: brackets (tags[0]) or separator (tags.0)
+    #[arg(long, value_parser = parse_index_style, default_value = "brackets")]
+    pub index_style: IndexStyle,
+
+    /// Mask columns before writing, e.g. "email=hash,phone=redact,name=fake"
+    #[arg(long, value_delimiter = ',', value_parser = parse_mask)]
+    pub mask: Vec<(String, MaskStrategy)>,
+
+    /// BLAKE3 key file for hash and fake masking, e.g. one made by `text generate`
+    #[arg(long, value_parser = verify_file)]
+    pub mask_key: Option<String>,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct CsvWriteOpts {
+    /// Output file, "-" for stdout. Defaults to output.<format>
     #[arg(short, long)]
     pub output: Option<String>,
 
     #[arg(long, value_parser = parse_format, default_value = "json")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
+    /// Table name used for the `[[table]]` entries of TOML output
+    #[arg(long, default_value = "record")]
+    pub table: String,
 
-    #[arg(long, default_value_t = true)]
-    pub header: bool,
+    /// Write one document per record: JSON Lines for json, a multi-document stream for yaml
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Compression for parquet (default snappy) and arrow (default none): none, snappy, gzip, zstd or lz4
+    #[arg(long, value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+
+    /// Records per parquet row group or arrow record batch
+    #[arg(long, default_value_t = 65536)]
+    pub row_group_size: usize,
+
+    /// Field delimiter of csv and tsv output. Defaults to ',' for csv and '\t' for tsv
+    #[arg(long, value_parser = parse_byte)]
+    pub out_delimiter: Option<u8>,
+
+    /// When csv and tsv output quotes fields: always, necessary, non-numeric or never
+    #[arg(long, value_parser = parse_quote_style, default_value = "necessary")]
+    pub quote_style: CsvQuoteStyle,
+
+    /// Record terminator of csv and tsv output: lf or crlf
+    #[arg(long, value_parser = parse_line_ending, default_value = "lf")]
+    pub line_ending: LineEnding,
+
+    /// Column types from `--types`, which parquet and arrow output use instead of inferring
+    #[arg(skip)]
+    pub types: HashMap<String, ColumnType>,
+}
+
+impl CsvWriteOpts {
+    pub fn output_path(&self) -> String {
+        match &self.output {
+            Some(output) => output.clone(),
+            None => format!("output.{}", self.format),
+        }
+    }
+}
+
+impl Default for CsvReadOpts {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            comment: None,
+            flexible: false,
+            trim: false,
+            header: true,
+            encoding: InputEncoding::Auto,
+        }
+    }
+}
+
+impl Default for CsvWriteOpts {
+    fn default() -> Self {
+        Self {
+            output: None,
+            format: OutputFormat::Json,
+            table: "record".to_string(),
+            stream: false,
+            compression: None,
+            row_group_size: 65536,
+            out_delimiter: None,
+            quote_style: CsvQuoteStyle::Necessary,
+            line_ending: LineEnding::Lf,
+            types: HashMap::new(),
+        }
+    }
+}
+
+fn parse_cell_range(value: &str) -> Result<CellRange, anyhow::Error> {
+    value.parse()
+}
+
+fn parse_sort_key(value: &str) -> Result<SortKey, anyhow::Error> {
+    value.parse()
+}
+
+pub(crate) fn parse_byte(value: &str) -> Result<u8, anyhow::Error> {
+    let c = match value {
+        "\\t" | "tab" => '\t',
+        "\\\\" => '\\',
+        v => {
+            let mut chars = v.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => anyhow::bail!("expect a single character, got {:?}", v),
+            }
+        }
+    };
+    if !c.is_ascii() {
+        anyhow::bail!("{:?} is not an ASCII character", c);
+    }
+    Ok(c as u8)
 }
 
 pub fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
@@ -40,7 +558,11 @@ impl From<OutputFormat> for &'static str {
         match format {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
-            //OutputFormat::Toml => "toml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
         }
     }
 }
@@ -51,8 +573,191 @@ impl FromStr for OutputFormat {
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         match value.to_lowercase().as_str() {
             "json" => Ok(OutputFormat::Json),
-            "yaml" => Ok(OutputFormat::Yaml),
-            //"toml" => Ok(OutputFormat::Toml),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "arrow" | "ipc" => Ok(OutputFormat::Arrow),
+            v => Err(anyhow::anyhow!("unknow type {:?}", v)),
+        }
+    }
+}
+
+fn parse_encoding(value: &str) -> Result<InputEncoding, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(InputEncoding::Auto);
+        }
+        Encoding::for_label(value.as_bytes())
+            .map(InputEncoding::Label)
+            .ok_or_else(|| anyhow::anyhow!("unknown encoding {:?}", value))
+    }
+}
+
+impl fmt::Display for InputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputEncoding::Auto => write!(f, "auto"),
+            InputEncoding::Label(encoding) => write!(f, "{}", encoding.name()),
+        }
+    }
+}
+
+fn parse_quote_style(value: &str) -> Result<CsvQuoteStyle, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for CsvQuoteStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "always" | "all" => Ok(CsvQuoteStyle::Always),
+            "necessary" => Ok(CsvQuoteStyle::Necessary),
+            "non-numeric" | "nonnumeric" => Ok(CsvQuoteStyle::NonNumeric),
+            "never" => Ok(CsvQuoteStyle::Never),
+            v => Err(anyhow::anyhow!("unknow quote style {:?}", v)),
+        }
+    }
+}
+
+fn parse_line_ending(value: &str) -> Result<LineEnding, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for LineEnding {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "lf" | "unix" => Ok(LineEnding::Lf),
+            "crlf" | "windows" => Ok(LineEnding::Crlf),
+            v => Err(anyhow::anyhow!("unknow line ending {:?}", v)),
+        }
+    }
+}
+
+fn parse_compression(value: &str) -> Result<Compression, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "none" | "uncompressed" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "lz4" => Ok(Compression::Lz4),
+            v => Err(anyhow::anyhow!("unknow compression {:?}", v)),
+        }
+    }
+}
+
+impl From<Compression> for &'static str {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => "none",
+            Compression::Snappy => "snappy",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for MaskStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "hash" => Ok(MaskStrategy::Hash),
+            "redact" => Ok(MaskStrategy::Redact),
+            "fake" => Ok(MaskStrategy::Fake),
+            v => Err(anyhow::anyhow!("unknow mask strategy {:?}", v)),
+        }
+    }
+}
+
+fn parse_index_style(value: &str) -> Result<IndexStyle, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for IndexStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "brackets" => Ok(IndexStyle::Brackets),
+            "separator" => Ok(IndexStyle::Separator),
+            v => Err(anyhow::anyhow!("unknow index style {:?}", v)),
+        }
+    }
+}
+
+fn parse_aggregate(value: &str) -> Result<Aggregate, anyhow::Error> {
+    value.parse()
+}
+
+fn parse_join_type(value: &str) -> Result<JoinType, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for JoinType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "inner" => Ok(JoinType::Inner),
+            "left" => Ok(JoinType::Left),
+            "outer" | "full" => Ok(JoinType::Outer),
+            v => Err(anyhow::anyhow!("unknow join type {:?}", v)),
+        }
+    }
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            v => Err(anyhow::anyhow!("unknow type {:?}", v)),
+        }
+    }
+}
+
+fn parse_stats_format(format: &str) -> Result<StatsFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "table" => Ok(StatsFormat::Table),
+            "json" => Ok(StatsFormat::Json),
             v => Err(anyhow::anyhow!("unknow type {:?}", v)),
         }
     }
@@ -66,11 +771,143 @@ impl fmt::Display for OutputFormat {
 
 impl CmdExector for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let output = if let Some(output) = self.output {
-            output
-        } else {
-            format!("output.{}", self.format)
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
+        if self.sheet.list_sheets {
+            return process_list_sheets(&self.input);
+        }
+        let types = self.types.unwrap_or_default();
+        let parser = CellParser::new(self.infer, types.clone()).with_date_format(self.date_format);
+        let write = CsvWriteOpts {
+            types,
+            ..self.write
         };
-        process_csv(&self.input, output, self.format)
+        process_csv(
+            &self.input,
+            &self.read,
+            &self.sheet,
+            &parser,
+            &self.transform,
+            &write,
+        )
+    }
+}
+
+impl CmdExector for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let split = match (self.rows, self.size, self.by_column) {
+            (Some(rows), _, _) => SplitBy::Rows(rows),
+            (_, Some(size), _) => SplitBy::Size(size),
+            (_, _, Some(column)) => SplitBy::Column(column),
+            _ => unreachable!("clap requires one of --rows, --size and --by-column"),
+        };
+        process_csv_split(&self.input, &split, &self.output_dir, &self.read)
+    }
+}
+
+impl CmdExector for CsvShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_show(
+            &self.input,
+            &self.read,
+            &self.select,
+            self.head,
+            self.tail,
+            self.max_width,
+        )
+    }
+}
+
+impl CmdExector for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_stats(&self.input, &self.read, self.approx, self.top, self.format)
+    }
+}
+
+impl CmdExector for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_validate(&self.input, &self.read, &self.schema)
+    }
+}
+
+impl CmdExector for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let types = self.types.unwrap_or_default();
+        let parser = CellParser::new(self.infer, types.clone()).with_date_format(self.date_format);
+        let write = CsvWriteOpts {
+            types,
+            ..self.write
+        };
+        process_csv_join(
+            &self.left,
+            &self.right,
+            &self.on,
+            self.how,
+            &self.read,
+            &parser,
+            &write,
+        )
+    }
+}
+
+impl CmdExector for CsvConcatOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let types = self.types.unwrap_or_default();
+        let parser = CellParser::new(self.infer, types.clone()).with_date_format(self.date_format);
+        let write = CsvWriteOpts {
+            types,
+            ..self.write
+        };
+        process_csv_concat(&self.inputs, &self.read, &parser, &write)
+    }
+}
+
+impl CmdExector for CsvAggOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_agg(
+            &self.input,
+            &self.read,
+            &self.group_by,
+            &self.agg,
+            &self.write,
+        )
+    }
+}
+
+impl CmdExector for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_query(&self.sql, &self.input, &self.read, &self.write)
+    }
+}
+
+impl CmdExector for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_diff(&self.old, &self.new, &self.key, &self.read, self.format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte() {
+        assert_eq!(parse_byte(";").unwrap(), b';');
+        assert_eq!(parse_byte("\\t").unwrap(), b'\t');
+        assert_eq!(parse_byte("\t").unwrap(), b'\t');
+        assert!(parse_byte(";;").is_err());
+        assert!(parse_byte("§").is_err());
+    }
+
+    #[test]
+    fn test_parse_encoding() {
+        assert!(matches!(
+            parse_encoding("AUTO").unwrap(),
+            InputEncoding::Auto
+        ));
+        assert_eq!(parse_encoding("gb2312").unwrap().to_string(), "GBK");
+        assert_eq!(parse_encoding("utf-16le").unwrap().to_string(), "UTF-16LE");
+        assert!(parse_encoding("klingon").is_err());
     }
 }
@@ -1,40 +1,221 @@
-use clap::Parser;
-use zxcvbn::zxcvbn;
+use clap::{ArgAction, Parser};
+use std::str::FromStr;
 
-use crate::{process_genpass, CmdExector};
+use crate::{
+    load_policy, load_wordlist, parse_charset, process_genpass, process_passphrase,
+    write_passwords, BuiltinClasses, CharKind, CmdExector, GeneratedPassword, PasswordRules,
+};
 
 #[derive(Debug, Parser)]
 pub struct GenPassOpts {
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
 
-    #[arg(long, default_value_t = true)]
+    /// Leave out uppercase letters
+    #[arg(long = "no-uppercase", action = ArgAction::SetFalse)]
     pub uppercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    /// Leave out lowercase letters
+    #[arg(long = "no-lowercase", action = ArgAction::SetFalse)]
     pub lowercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    /// Leave out digits
+    #[arg(long = "no-numbers", action = ArgAction::SetFalse)]
     pub numbers: bool,
 
-    #[arg(long, default_value_t = true)]
+    /// Leave out symbols
+    #[arg(long = "no-symbol", action = ArgAction::SetFalse)]
     pub symbol: bool,
+
+    /// Draw from these characters instead of the built-in sets, e.g. 'a-z0-9'. Can be repeated;
+    /// every charset is required once
+    #[arg(long, allow_hyphen_values = true, conflicts_with_all = ["uppercase", "lowercase", "numbers", "symbol", "symbols", "allow_ambiguous"])]
+    pub charset: Vec<String>,
+
+    /// Symbols to use instead of the built-in "!@#$%^&*_"
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "symbol")]
+    pub symbols: Option<String>,
+
+    /// Characters that never appear in the password, e.g. '0O1lI'
+    #[arg(long, allow_hyphen_values = true, default_value = "")]
+    pub exclude: String,
+
+    /// Minimum number of uppercase letters
+    #[arg(long)]
+    pub min_upper: Option<usize>,
+
+    /// Minimum number of lowercase letters
+    #[arg(long)]
+    pub min_lower: Option<usize>,
+
+    /// Minimum number of digits
+    #[arg(long)]
+    pub min_digits: Option<usize>,
+
+    /// Minimum number of symbols
+    #[arg(long)]
+    pub min_symbols: Option<usize>,
+
+    /// Never put the same character twice in a row
+    #[arg(long)]
+    pub no_repeat: bool,
+
+    /// Keep look-alike characters such as 0, O, l and I in the built-in sets
+    #[arg(long)]
+    pub allow_ambiguous: bool,
+
+    /// Generate for a named policy from --policy-file, e.g. corp-ldap. --length is moved into its range
+    #[arg(long, conflicts_with_all = ["uppercase", "lowercase", "numbers", "symbol", "charset", "symbols",
+        "min_upper", "min_lower", "min_digits", "min_symbols", "no_repeat", "allow_ambiguous", "passphrase"])]
+    pub policy: Option<String>,
+
+    /// TOML or YAML file of named password policies
+    #[arg(long, default_value = "policies.toml", requires = "policy")]
+    pub policy_file: String,
+
+    /// Keep generating until zxcvbn scores the password at least this, 0 to 4
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: Option<u8>,
+
+    /// Generate a diceware passphrase from the EFF large wordlist instead
+    #[arg(long, conflicts_with_all = ["length", "uppercase", "lowercase", "numbers", "symbol", "charset",
+        "symbols", "exclude", "min_upper", "min_lower", "min_digits", "min_symbols", "no_repeat",
+        "allow_ambiguous", "min_score"])]
+    pub passphrase: bool,
+
+    /// Diceware wordlist to use instead of the bundled EFF large wordlist, one
+    /// `11111<tab>word` line per roll of five dice
+    #[arg(long, requires = "passphrase")]
+    pub wordlist: Option<String>,
+
+    /// Number of words in the passphrase
+    #[arg(long, default_value_t = 6, requires = "passphrase")]
+    pub words: u8,
+
+    /// Separator between passphrase words
+    #[arg(long, default_value = "-", requires = "passphrase")]
+    pub separator: String,
+
+    /// Capitalize every passphrase word
+    #[arg(long, requires = "passphrase")]
+    pub capitalize: bool,
+
+    /// Append this many random digits to random passphrase words
+    #[arg(long, default_value_t = 0, requires = "passphrase")]
+    pub digits: u8,
+
+    /// Number of passwords to generate
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// plain prints one password per line, json and csv add score, crack time and entropy
+    #[arg(long, value_parser = parse_genpass_format, default_value = "plain")]
+    pub format: GenPassFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenPassFormat {
+    Plain,
+    Json,
+    Csv,
 }
 
 impl CmdExector for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let ret = process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.numbers,
-            self.symbol,
-        )?;
-        println!("{}", ret);
-
-        let estimate = zxcvbn(&ret, &[]);
-        eprint!("strength {}", estimate.score());
+        let mut passwords = Vec::with_capacity(self.count as usize);
+        if self.passphrase {
+            let list = load_wordlist(self.wordlist.as_deref())?;
+            for _ in 0..self.count {
+                let (ret, entropy) = process_passphrase(
+                    &list,
+                    self.words,
+                    &self.separator,
+                    self.capitalize,
+                    self.digits,
+                )?;
+                passwords.push(GeneratedPassword::new(ret, entropy));
+            }
+        } else {
+            let rules = self.rules()?;
+            let entropy = rules.entropy();
+            for _ in 0..self.count {
+                passwords.push(GeneratedPassword::new(process_genpass(&rules)?, entropy));
+            }
+        }
 
+        write_passwords(&mut std::io::stdout().lock(), &passwords, self.format)?;
+        if self.format == GenPassFormat::Plain {
+            for password in &passwords {
+                eprintln!(
+                    "strength {}, crack time {}, entropy {:.1} bits",
+                    password.score, password.crack_time, password.entropy
+                );
+            }
+        }
         Ok(())
     }
 }
+
+impl GenPassOpts {
+    fn rules(&self) -> anyhow::Result<PasswordRules> {
+        let length = self.length as usize;
+        let mut rules = if let Some(policy) = &self.policy {
+            load_policy(&self.policy_file, policy)?.rules(length)?
+        } else if self.charset.is_empty() {
+            let builtin = BuiltinClasses {
+                upper: self.uppercase,
+                lower: self.lowercase,
+                digit: self.numbers,
+                symbol: self.symbol,
+                ambiguous: self.allow_ambiguous,
+            };
+            PasswordRules::new(length, builtin)
+        } else {
+            let charsets = self
+                .charset
+                .iter()
+                .map(|spec| parse_charset(spec))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            PasswordRules::from_charsets(length, &charsets)
+        };
+        if let Some(symbols) = &self.symbols {
+            rules.set_symbols(symbols);
+        }
+        let mins = [
+            (CharKind::Upper, self.min_upper),
+            (CharKind::Lower, self.min_lower),
+            (CharKind::Digit, self.min_digits),
+            (CharKind::Symbol, self.min_symbols),
+        ];
+        for (kind, min) in mins {
+            if let Some(min) = min {
+                rules.require(kind, min)?;
+            }
+        }
+        rules.exclude.extend(self.exclude.chars());
+        if self.no_repeat {
+            rules.max_repeat = Some(1);
+        }
+        if self.min_score.is_some() {
+            rules.min_score = self.min_score;
+        }
+        Ok(rules)
+    }
+}
+
+fn parse_genpass_format(format: &str) -> Result<GenPassFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for GenPassFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "plain" => Ok(GenPassFormat::Plain),
+            "json" => Ok(GenPassFormat::Json),
+            "csv" => Ok(GenPassFormat::Csv),
+            v => Err(anyhow::anyhow!("unknow type {:?}", v)),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod base64;
+mod convert;
 mod csv;
 mod genpass;
 mod http;
@@ -6,11 +7,14 @@ mod text;
 
 use std::path::{Path, PathBuf};
 
-pub use self::{csv::CsvOpts, genpass::GenPassOpts};
+pub use self::{
+    csv::CsvOpts,
+    genpass::{GenPassFormat, GenPassOpts},
+};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-pub use self::{base64::*, csv::*, http::*, text::*};
+pub use self::{base64::*, convert::*, csv::*, http::*, text::*};
 
 #[derive(Debug, Parser)]
 #[command(name = "rcli", version, author, about, long_about = None)]
@@ -19,11 +23,15 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// parsed once at startup, boxing the csv options isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum SubCommand {
     #[command(name = "csv", about = "Show CSV, or convert CSV to other formats")]
     Csv(CsvOpts),
+    #[command(name = "convert", about = "Convert JSON, YAML or TOML records to CSV")]
+    Convert(ConvertOpts),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
     #[command(subcommand, about = "Base64 encode/decode")]
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, bail, Result};
+use csv::WriterBuilder;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::{cli::OutputFormat, get_content, get_writer};
+
+pub fn process_convert(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    separator: &str,
+    table: Option<&str>,
+    delimiter: u8,
+) -> Result<()> {
+    let content = get_content(input)?;
+    let records = read_records(&content, format, table)?;
+    let (columns, rows) = flatten_records(&records, separator)?;
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(get_writer(output)?);
+    writer.write_record(&columns)?;
+    for mut row in rows {
+        let record = columns
+            .iter()
+            .map(|column| row.remove(column).unwrap_or_default());
+        writer.write_record(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Load the list of records from a document: a JSON array or JSON Lines, a YAML sequence or
+/// multi-document stream, or a TOML array of tables.
+pub fn read_records(
+    content: &[u8],
+    format: OutputFormat,
+    table: Option<&str>,
+) -> Result<Vec<Value>> {
+    let values = match format {
+        OutputFormat::Json => serde_json::Deserializer::from_slice(content)
+            .into_iter::<Value>()
+            .collect::<Result<Vec<_>, _>>()?,
+        OutputFormat::Yaml => serde_yaml::Deserializer::from_slice(content)
+            .map(Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()?,
+        OutputFormat::Toml => {
+            let doc: toml::Table = toml::from_str(std::str::from_utf8(content)?)?;
+            let Value::Object(doc) = toml_to_json(toml::Value::Table(doc)) else {
+                unreachable!("a TOML document is a table");
+            };
+            vec![select_table(doc, table)?]
+        }
+        OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::Parquet | OutputFormat::Arrow => {
+            bail!("{} is not supported as input", format)
+        }
+    };
+    match <[Value; 1]>::try_from(values) {
+        Ok([Value::Array(records)]) => Ok(records),
+        Ok([record]) => Ok(vec![record]),
+        Err(values) => Ok(values),
+    }
+}
+
+/// TOML datetimes have no JSON counterpart and become their RFC 3339 string.
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        toml::Value::Datetime(dt) => dt.to_string().into(),
+        toml::Value::Array(arr) => arr.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn select_table(mut doc: Map<String, Value>, table: Option<&str>) -> Result<Value> {
+    if let Some(table) = table {
+        return doc
+            .remove(table)
+            .ok_or_else(|| anyhow!("table {:?} not found", table));
+    }
+    // a document holding a single array of tables, e.g. `[[record]]`
+    match doc.values().next() {
+        Some(Value::Array(records)) if doc.len() == 1 => Ok(Value::Array(records.clone())),
+        _ => Ok(Value::Object(doc)),
+    }
+}
+
+pub type FlatRow = HashMap<String, String>;
+
+/// Flatten records into rows keyed by column name. Columns are the union of all keys, in the
+/// order they are first seen.
+pub fn flatten_records(records: &[Value], separator: &str) -> Result<(Vec<String>, Vec<FlatRow>)> {
+    let mut columns = Vec::new();
+    let mut seen = HashSet::new();
+    let mut rows = Vec::with_capacity(records.len());
+    for (i, record) in records.iter().enumerate() {
+        let mut row = HashMap::new();
+        let mut order = Vec::new();
+        flatten_value(record, String::new(), separator, &mut row, &mut order)
+            .map_err(|e| anyhow!("record {}: {}", i + 1, e))?;
+        for column in order {
+            if seen.insert(column.clone()) {
+                columns.push(column);
+            }
+        }
+        rows.push(row);
+    }
+    Ok((columns, rows))
+}
+
+// `order` keeps the keys in the order they are flattened
+fn flatten_value(
+    value: &Value,
+    prefix: String,
+    separator: &str,
+    row: &mut HashMap<String, String>,
+    order: &mut Vec<String>,
+) -> Result<()> {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}{}{}", prefix, separator, key)
+        }
+    };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                flatten_value(v, join(k), separator, row, order)?;
+            }
+            return Ok(());
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                flatten_value(v, join(&i.to_string()), separator, row, order)?;
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    let cell = match value {
+        Value::String(s) => s.clone(),
+        Value::Object(_) | Value::Array(_) | Value::Null => String::new(),
+        v => v.to_string(),
+    };
+    if row.insert(prefix.clone(), cell).is_some() {
+        bail!("key {:?} appears twice after flattening", prefix);
+    }
+    order.push(prefix);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_records() -> Result<()> {
+        let records = read_records(b"[{\"a\": 1}, {\"a\": 2}]", OutputFormat::Json, None)?;
+        assert_eq!(records.len(), 2);
+        let records = read_records(b"{\"a\": 1}\n{\"a\": 2}\n", OutputFormat::Json, None)?;
+        assert_eq!(records.len(), 2);
+        let records = read_records(b"a: 1\n---\na: 2\n", OutputFormat::Yaml, None)?;
+        assert_eq!(records, vec![json!({"a": 1}), json!({"a": 2})]);
+        let records = read_records(
+            b"[[record]]\na = 1\n[[record]]\na = 2\n",
+            OutputFormat::Toml,
+            None,
+        )?;
+        assert_eq!(records, vec![json!({"a": 1}), json!({"a": 2})]);
+        let records = read_records(
+            b"[[record]]\nname = \"Perin\"\ndob = 1992-11-10\n",
+            OutputFormat::Toml,
+            None,
+        )?;
+        assert_eq!(records, vec![json!({"name": "Perin", "dob": "1992-11-10"})]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_records() {
+        let records = vec![
+            json!({"name": "Perin", "address": {"city": "Turin"}, "tags": ["gk"]}),
+            json!({"name": "Buffon", "kit": 77, "address": {"city": null}}),
+        ];
+        let (columns, rows) = flatten_records(&records, ".").unwrap();
+        assert_eq!(columns, vec!["name", "address.city", "tags.0", "kit"]);
+        assert_eq!(rows[0]["address.city"], "Turin");
+        assert_eq!(rows[1]["kit"], "77");
+        assert_eq!(rows[1]["address.city"], "");
+        assert!(!rows[1].contains_key("tags.0"));
+
+        let records = vec![json!({"a.b": 1, "a": {"b": 2}})];
+        assert!(flatten_records(&records, ".").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{anyhow, bail, Result};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, collections::HashMap, fmt, str::FromStr};
+
+use super::csv_filter::{as_number, as_text};
+use crate::{
+    build_reader, compare_values, get_reader, read_headers, record_to_value, write_output,
+    write_records, CellParser, CsvReadOpts, CsvWriteOpts,
+};
+
+/// One `--agg` entry such as `count(*)` or `avg(Kit Number)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate {
+    pub func: AggFunc,
+    /// `None` for `count(*)`
+    pub column: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    First,
+    Last,
+}
+
+pub fn process_csv_agg(
+    input: &str,
+    opts: &CsvReadOpts,
+    group_by: &[String],
+    aggs: &[Aggregate],
+    write: &CsvWriteOpts,
+) -> Result<()> {
+    let mut reader = build_reader(get_reader(input)?, opts)?;
+    let headers = read_headers(&mut reader, opts)?;
+    let parser = CellParser::default();
+    let records = reader
+        .into_records()
+        .map(|record| record_to_value(&headers, &record?, &parser));
+    let groups = aggregate_records(records, &headers, group_by, aggs)?;
+    write_output(&write.output_path(), |writer| {
+        write_records(writer, groups.into_iter().map(Ok), write)
+    })
+}
+
+/// Fold the records into one row per distinct `group_by` key, in order of first appearance.
+/// Without `group_by` the whole input is a single group.
+pub fn aggregate_records<I>(
+    records: I,
+    headers: &StringRecord,
+    group_by: &[String],
+    aggs: &[Aggregate],
+) -> Result<Vec<Value>>
+where
+    I: Iterator<Item = Result<Value>>,
+{
+    let columns = group_by
+        .iter()
+        .chain(aggs.iter().filter_map(|a| a.column.as_ref()));
+    for column in columns {
+        if !headers.iter().any(|h| h == column) {
+            bail!("column {:?} not found", column);
+        }
+    }
+    let names: Vec<String> = group_by
+        .iter()
+        .cloned()
+        .chain(aggs.iter().map(|agg| agg.to_string()))
+        .collect();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            bail!("output column {:?} appears twice", name);
+        }
+    }
+
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+    for record in records {
+        let Value::Object(record) = record? else {
+            bail!("expect a header-keyed record");
+        };
+        let values: Vec<Value> = group_by
+            .iter()
+            .map(|column| record.get(column).cloned().unwrap_or_default())
+            .collect();
+        let key = values.iter().map(as_text).collect();
+        let i = *index.entry(key).or_insert_with(|| {
+            let accumulators = aggs.iter().map(|agg| Accumulator::new(agg.func)).collect();
+            groups.push((values, accumulators));
+            groups.len() - 1
+        });
+        for (agg, accumulator) in aggs.iter().zip(&mut groups[i].1) {
+            match &agg.column {
+                Some(column) => accumulator.update(record.get(column).unwrap_or(&Value::Null)),
+                // count(*) counts rows, not values
+                None => accumulator.update(&Value::Bool(true)),
+            }
+        }
+    }
+    if group_by.is_empty() && groups.is_empty() {
+        let accumulators = aggs.iter().map(|agg| Accumulator::new(agg.func)).collect();
+        groups.push((vec![], accumulators));
+    }
+
+    let rows = groups
+        .into_iter()
+        .map(|(values, accumulators)| {
+            let mut row: Map<String, Value> = group_by.iter().cloned().zip(values).collect();
+            for (agg, accumulator) in aggs.iter().zip(accumulators) {
+                row.insert(agg.to_string(), accumulator.finish());
+            }
+            Value::Object(row)
+        })
+        .collect();
+    Ok(rows)
+}
+
+enum Accumulator {
+    Count(u64),
+    Sum(Option<f64>),
+    Avg(f64, u64),
+    Extreme(Option<Value>, Ordering),
+    First(Option<Value>),
+    Last(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(func: AggFunc) -> Self {
+        match func {
+            AggFunc::Count => Accumulator::Count(0),
+            AggFunc::Sum => Accumulator::Sum(None),
+            AggFunc::Avg => Accumulator::Avg(0.0, 0),
+            AggFunc::Min => Accumulator::Extreme(None, Ordering::Less),
+            AggFunc::Max => Accumulator::Extreme(None, Ordering::Greater),
+            AggFunc::First => Accumulator::First(None),
+            AggFunc::Last => Accumulator::Last(None),
+        }
+    }
+
+    /// Empty cells and nulls are skipped by every function.
+    fn update(&mut self, value: &Value) {
+        if matches!(value, Value::Null) || value.as_str() == Some("") {
+            return;
+        }
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                if let Some(n) = as_number(value) {
+                    *sum = Some(sum.unwrap_or_default() + n);
+                }
+            }
+            Accumulator::Avg(sum, count) => {
+                if let Some(n) = as_number(value) {
+                    *sum += n;
+                    *count += 1;
+                }
+            }
+            Accumulator::Extreme(current, ordering) => {
+                if current
+                    .as_ref()
+                    .is_none_or(|current| compare_values(value, current) == *ordering)
+                {
+                    *current = Some(value.clone());
+                }
+            }
+            Accumulator::First(first) => {
+                if first.is_none() {
+                    *first = Some(value.clone());
+                }
+            }
+            Accumulator::Last(last) => *last = Some(value.clone()),
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(count) => count.into(),
+            Accumulator::Sum(sum) => sum.map(number_value).unwrap_or_default(),
+            Accumulator::Avg(_, 0) => Value::Null,
+            Accumulator::Avg(sum, count) => number_value(sum / count as f64),
+            Accumulator::Extreme(value, _)
+            | Accumulator::First(value)
+            | Accumulator::Last(value) => value.unwrap_or_default(),
+        }
+    }
+}
+
+// whole numbers are written without a trailing `.0`
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        (n as i64).into()
+    } else {
+        n.into()
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (func, column) = s
+            .trim()
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(|| anyhow!("invalid aggregate {:?}, expect func(column)", s))?;
+        let func: AggFunc = func.trim().parse()?;
+        let column = match column.trim() {
+            "*" if func == AggFunc::Count => None,
+            "*" => bail!("only count accepts *"),
+            "" => bail!("missing column in {:?}", s),
+            column => Some(column.to_string()),
+        };
+        Ok(Aggregate { func, column })
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let func: &str = self.func.into();
+        write!(f, "{}({})", func, self.column.as_deref().unwrap_or("*"))
+    }
+}
+
+impl FromStr for AggFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "count" => Ok(AggFunc::Count),
+            "sum" => Ok(AggFunc::Sum),
+            "avg" | "mean" => Ok(AggFunc::Avg),
+            "min" => Ok(AggFunc::Min),
+            "max" => Ok(AggFunc::Max),
+            "first" => Ok(AggFunc::First),
+            "last" => Ok(AggFunc::Last),
+            v => Err(anyhow!("unknown aggregate function {:?}", v)),
+        }
+    }
+}
+
+impl From<AggFunc> for &'static str {
+    fn from(func: AggFunc) -> Self {
+        match func {
+            AggFunc::Count => "count",
+            AggFunc::Sum => "sum",
+            AggFunc::Avg => "avg",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+            AggFunc::First => "first",
+            AggFunc::Last => "last",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregate_records() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Position", "DOB", "Kit Number"]);
+        let records = vec![
+            json!({"Name": "Szczesny", "Position": "Goalkeeper", "DOB": "1990-04-18", "Kit Number": "1"}),
+            json!({"Name": "Chiellini", "Position": "Centre-Back", "DOB": "1984-08-14", "Kit Number": "3"}),
+            json!({"Name": "Buffon", "Position": "Goalkeeper", "DOB": "1978-01-28", "Kit Number": "77"}),
+            json!({"Name": "Perin", "Position": "Goalkeeper", "DOB": "1992-11-10", "Kit Number": ""}),
+        ];
+        let aggs: Vec<Aggregate> = [
+            "count(*)",
+            "min(DOB)",
+            "avg(Kit Number)",
+            "count(Kit Number)",
+        ]
+        .iter()
+        .map(|s| s.parse())
+        .collect::<Result<_>>()?;
+        let ret = aggregate_records(
+            records.into_iter().map(Ok),
+            &headers,
+            &["Position".to_string()],
+            &aggs,
+        )?;
+        assert_eq!(
+            ret,
+            vec![
+                json!({"Position": "Goalkeeper", "count(*)": 3, "min(DOB)": "1978-01-28", "avg(Kit Number)": 39, "count(Kit Number)": 2}),
+                json!({"Position": "Centre-Back", "count(*)": 1, "min(DOB)": "1984-08-14", "avg(Kit Number)": 3, "count(Kit Number)": 1}),
+            ]
+        );
+
+        let ret = aggregate_records(std::iter::empty(), &headers, &[], &aggs[..1])?;
+        assert_eq!(ret, vec![json!({"count(*)": 0})]);
+
+        let twice = [aggs[0].clone(), aggs[0].clone()];
+        assert!(aggregate_records(std::iter::empty(), &headers, &[], &twice).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_aggregate() {
+        let agg: Aggregate = " max( Kit Number )".parse().unwrap();
+        assert_eq!(agg.func, AggFunc::Max);
+        assert_eq!(agg.to_string(), "max(Kit Number)");
+        assert!("sum(*)".parse::<Aggregate>().is_err());
+        assert!("median(Age)".parse::<Aggregate>().is_err());
+        assert!("count".parse::<Aggregate>().is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use anyhow::{anyhow, bail, Result};
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use chrono::NaiveDate;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{GzipLevel, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, io::Write, sync::Arc};
+
+use super::{csv_filter::as_text, csv_stats::merge_types};
+use crate::{cli::OutputFormat, infer_type, parse_as, ColumnType, Compression, CsvWriteOpts};
+
+/// Write records as Parquet or an Arrow IPC file, one row group or record batch per
+/// `--row-group-size` records. Column types come from `--types`, or are inferred from the first
+/// batch; a later record that doesn't fit is an error naming its row.
+pub fn write_columnar<W, I>(writer: W, mut records: I, opts: &CsvWriteOpts) -> Result<()>
+where
+    W: Write + Send,
+    I: Iterator<Item = Result<Value>>,
+{
+    let batch_size = opts.row_group_size.max(1);
+    let mut batch = next_batch(&mut records, batch_size)?;
+    let schema = infer_schema(&batch, &opts.types);
+
+    let mut sink = match opts.format {
+        OutputFormat::Parquet => {
+            let compression = match opts.compression.unwrap_or(Compression::Snappy) {
+                Compression::None => parquet::basic::Compression::UNCOMPRESSED,
+                Compression::Snappy => parquet::basic::Compression::SNAPPY,
+                Compression::Gzip => parquet::basic::Compression::GZIP(GzipLevel::default()),
+                Compression::Zstd => parquet::basic::Compression::ZSTD(ZstdLevel::default()),
+                Compression::Lz4 => parquet::basic::Compression::LZ4_RAW,
+            };
+            let props = WriterProperties::builder()
+                .set_compression(compression)
+                .set_max_row_group_size(batch_size)
+                .build();
+            Sink::Parquet(ArrowWriter::try_new(writer, schema.clone(), Some(props))?)
+        }
+        _ => {
+            let compression = match opts.compression.unwrap_or(Compression::None) {
+                Compression::None => None,
+                Compression::Lz4 => Some(arrow_ipc::CompressionType::LZ4_FRAME),
+                Compression::Zstd => Some(arrow_ipc::CompressionType::ZSTD),
+                c => bail!("arrow output does not support {} compression", c),
+            };
+            let options = IpcWriteOptions::default().try_with_compression(compression)?;
+            Sink::Arrow(FileWriter::try_new_with_options(writer, &schema, options)?)
+        }
+    };
+
+    let mut offset = 0;
+    while !batch.is_empty() {
+        let record_batch = to_record_batch(&schema, &batch, offset)?;
+        offset += batch.len();
+        match &mut sink {
+            Sink::Parquet(writer) => writer.write(&record_batch)?,
+            Sink::Arrow(writer) => writer.write(&record_batch)?,
+        }
+        batch = next_batch(&mut records, batch_size)?;
+    }
+    match sink {
+        Sink::Parquet(writer) => {
+            writer.close()?;
+        }
+        Sink::Arrow(mut writer) => writer.finish()?,
+    }
+    Ok(())
+}
+
+enum Sink<W: Write + Send> {
+    Parquet(ArrowWriter<W>),
+    Arrow(FileWriter<W>),
+}
+
+fn next_batch<I>(records: &mut I, size: usize) -> Result<Vec<Map<String, Value>>>
+where
+    I: Iterator<Item = Result<Value>>,
+{
+    records
+        .take(size)
+        .map(|record| match record? {
+            Value::Object(map) => Ok(map),
+            _ => bail!("columnar output expects header-keyed records"),
+        })
+        .collect()
+}
+
+/// Columns are the keys of the batch in order of first appearance. Columns in `types` get that
+/// type, typed JSON values keep theirs and string cells are inferred like `--infer` does.
+/// Every column is nullable.
+pub fn infer_schema(
+    batch: &[Map<String, Value>],
+    types: &HashMap<String, ColumnType>,
+) -> SchemaRef {
+    let mut columns: Vec<(String, Option<ColumnType>, bool)> = Vec::new();
+    for record in batch {
+        for (key, value) in record {
+            let i = match columns.iter().position(|(name, _, _)| name == key) {
+                Some(i) => i,
+                None => {
+                    columns.push((key.clone(), None, true));
+                    columns.len() - 1
+                }
+            };
+            let (_, ty, plain_date) = &mut columns[i];
+            let value_ty = match (types.get(key), value) {
+                (Some(explicit), _) => Some(*explicit),
+                (None, Value::Null) => None,
+                (None, Value::Bool(_)) => Some(ColumnType::Bool),
+                (None, Value::Number(n)) if n.is_i64() || n.is_u64() => Some(ColumnType::Int),
+                (None, Value::Number(_)) => Some(ColumnType::Float),
+                (None, Value::String(s)) => infer_type(s),
+                (None, Value::Array(_) | Value::Object(_)) => Some(ColumnType::String),
+            };
+            if let Some(value_ty) = value_ty {
+                if value_ty == ColumnType::Date {
+                    *plain_date &= value.as_str().is_none_or(|s| s.is_empty() || s.len() == 10);
+                }
+                *ty = Some(merge_types(*ty, value_ty));
+            }
+        }
+    }
+    let fields: Vec<Field> = columns
+        .into_iter()
+        .map(|(name, ty, plain_date)| {
+            let data_type = match ty {
+                Some(ColumnType::Int) => DataType::Int64,
+                Some(ColumnType::Float) => DataType::Float64,
+                Some(ColumnType::Bool) => DataType::Boolean,
+                // timestamps have no single agreed-upon timezone, keep them as text
+                Some(ColumnType::Date) if plain_date => DataType::Date32,
+                _ => DataType::Utf8,
+            };
+            Field::new(name, data_type, true)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+// `offset` is the number of records written before this batch, for error messages
+fn to_record_batch(
+    schema: &SchemaRef,
+    batch: &[Map<String, Value>],
+    offset: usize,
+) -> Result<RecordBatch> {
+    for (i, record) in batch.iter().enumerate() {
+        if let Some(key) = record.keys().find(|k| schema.field_with_name(k).is_err()) {
+            bail!(
+                "row {}: column {:?} is not in the schema inferred from the first rows, \
+                 raise --row-group-size",
+                offset + i + 1,
+                key
+            );
+        }
+    }
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = field.name();
+            let text = field.data_type() == &DataType::Utf8;
+            // an empty cell is null in a typed column but stays "" in a text column
+            let values = batch.iter().enumerate().map(|(i, record)| {
+                let value = record
+                    .get(name)
+                    .filter(|v| !v.is_null() && (text || v.as_str() != Some("")));
+                (offset + i + 1, value)
+            });
+            let typed = |ty: ColumnType| {
+                values
+                    .clone()
+                    .map(move |(row, v)| v.map(|v| as_typed(v, ty, name, row)).transpose())
+            };
+            let column = match field.data_type() {
+                DataType::Int64 => Arc::new(
+                    typed(ColumnType::Int)
+                        .map(|v| Ok(v?.and_then(|v| v.as_i64())))
+                        .collect::<Result<Int64Array>>()?,
+                ) as ArrayRef,
+                DataType::Float64 => Arc::new(
+                    typed(ColumnType::Float)
+                        .map(|v| Ok(v?.and_then(|v| v.as_f64())))
+                        .collect::<Result<Float64Array>>()?,
+                ),
+                DataType::Boolean => Arc::new(
+                    typed(ColumnType::Bool)
+                        .map(|v| Ok(v?.and_then(|v| v.as_bool())))
+                        .collect::<Result<BooleanArray>>()?,
+                ),
+                DataType::Date32 => Arc::new(
+                    values
+                        .map(|(row, v)| v.map(|v| days_since_epoch(v, name, row)).transpose())
+                        .collect::<Result<Date32Array>>()?,
+                ),
+                _ => Arc::new(values.map(|(_, v)| v.map(as_text)).collect::<StringArray>()),
+            };
+            Ok(column)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn as_typed(value: &Value, ty: ColumnType, column: &str, row: usize) -> Result<Value> {
+    let typed = match (value, ty) {
+        (Value::String(s), _) => parse_as(s, ty).ok(),
+        (Value::Number(n), ColumnType::Int) if n.is_i64() => Some(value.clone()),
+        (Value::Number(_), ColumnType::Float) | (Value::Bool(_), ColumnType::Bool) => {
+            Some(value.clone())
+        }
+        _ => None,
+    };
+    typed.ok_or_else(|| {
+        anyhow!(
+            "row {}: column {:?}: {} is not {} like the first rows, pass --types or raise --row-group-size",
+            row,
+            column,
+            value,
+            ty
+        )
+    })
+}
+
+fn days_since_epoch(value: &Value, column: &str, row: usize) -> Result<i32> {
+    let date = value
+        .as_str()
+        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+        .ok_or_else(|| anyhow!("row {}: column {:?}: {} is not a date", row, column, value))?;
+    Ok((date - NaiveDate::default()).num_days() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use arrow_ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "Buffon", "Kit Number": "77", "Height": "1.92", "Captain": "true", "DOB": "1978-01-28"}),
+            json!({"Name": "Perin", "Kit Number": 37, "Height": "", "Captain": false, "DOB": "1992-11-10"}),
+            json!({"Name": "Szczesny", "Kit Number": "1", "Height": 1.95, "Captain": null, "DOB": ""}),
+        ]
+    }
+
+    fn write_opts(format: OutputFormat) -> CsvWriteOpts {
+        CsvWriteOpts {
+            format,
+            row_group_size: 2,
+            compression: Some(Compression::Zstd),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_write_parquet() -> Result<()> {
+        let mut buf = Vec::new();
+        let opts = write_opts(OutputFormat::Parquet);
+        write_columnar(&mut buf, records().into_iter().map(Ok), &opts)?;
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(buf))?;
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let schema = builder.schema().clone();
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                DataType::Utf8,
+                DataType::Int64,
+                DataType::Float64,
+                DataType::Boolean,
+                DataType::Date32
+            ]
+        );
+        let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+        let kits = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(kits.values(), &[77, 37, 1]);
+        assert!(batches[0].column(3).is_null(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_arrow() -> Result<()> {
+        let mut buf = Vec::new();
+        let opts = write_opts(OutputFormat::Arrow);
+        write_columnar(&mut buf, records().into_iter().map(Ok), &opts)?;
+
+        let reader = FileReader::try_new(Cursor::new(buf), None)?;
+        let rows: usize = reader
+            .map(|b| b.map(|b| b.num_rows()))
+            .sum::<Result<_, _>>()?;
+        assert_eq!(rows, 3);
+
+        let opts = CsvWriteOpts {
+            compression: Some(Compression::Snappy),
+            ..write_opts(OutputFormat::Arrow)
+        };
+        assert!(write_columnar(Vec::new(), std::iter::empty(), &opts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_columnar_type_mismatch() {
+        let records = vec![json!({"Kit Number": "1"}), json!({"Kit Number": "x"})];
+        let opts = CsvWriteOpts {
+            row_group_size: 1,
+            ..write_opts(OutputFormat::Parquet)
+        };
+        let err = write_columnar(Vec::new(), records.into_iter().map(Ok), &opts).unwrap_err();
+        assert!(err.to_string().starts_with("row 2: column \"Kit Number\""));
+    }
+
+    #[test]
+    fn test_write_columnar_explicit_types() -> Result<()> {
+        let records = vec![
+            json!({"zip": "00501", "id": "1", "note": ""}),
+            json!({"zip": "10151", "id": "", "note": "moved"}),
+        ];
+        let opts = CsvWriteOpts {
+            types: crate::parse_types("zip=string")?,
+            ..write_opts(OutputFormat::Arrow)
+        };
+        let mut buf = Vec::new();
+        write_columnar(&mut buf, records.into_iter().map(Ok), &opts)?;
+
+        let batch = FileReader::try_new(Cursor::new(buf), None)?
+            .next()
+            .unwrap()?;
+        let types: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        assert_eq!(types, vec![DataType::Utf8, DataType::Int64, DataType::Utf8]);
+        let notes = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(notes.is_valid(0));
+        assert_eq!(notes.value(0), "");
+        assert!(batch.column(1).is_null(1));
+        Ok(())
+    }
+}
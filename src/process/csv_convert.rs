@@ -1,43 +1,366 @@
 use anyhow::Result;
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::fs;
-
-use crate::cli::OutputFormat;
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    //#[serde(rename = "Name")]
-    name: String,
-    //#[serde(rename = "Position")]
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    //#[serde(rename = "Nationality")]
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        println!("{:?}", record);
-        ret.push(json_value); // borrowd
-    }
-
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-        //OutputFormat::Toml => toml::to_string(&ret)?,
-    };
-    //let json = serde_json::to_string_pretty(&ret)?;
-    println!("out path:{:?}", output);
-    fs::write(output, content)?;
+use csv::{QuoteStyle, Reader, ReaderBuilder, StringRecord, Terminator, Trim, WriterBuilder};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::io::{Read, Write};
+
+use super::{csv_columnar::write_columnar, csv_filter::as_text};
+use crate::{
+    cli::OutputFormat, decode_reader, get_reader, is_spreadsheet, read_sheet, transform_records,
+    write_output, CellParser, CsvQuoteStyle, CsvReadOpts, CsvTransformOpts, CsvWriteOpts,
+    LineEnding, SheetOpts,
+};
+
+pub fn process_csv(
+    input: &str,
+    opts: &CsvReadOpts,
+    sheet: &SheetOpts,
+    parser: &CellParser,
+    transform: &CsvTransformOpts,
+    write: &CsvWriteOpts,
+) -> Result<()> {
+    let (headers, rows): (StringRecord, Box<dyn Iterator<Item = Result<StringRecord>>>) =
+        if is_spreadsheet(input) {
+            let (headers, rows) = read_sheet(input, sheet, opts)?;
+            (headers, Box::new(rows.into_iter().map(Ok)))
+        } else {
+            if sheet.sheet.is_some() || sheet.range.is_some() {
+                anyhow::bail!("--sheet and --range only apply to spreadsheet inputs");
+            }
+            let mut reader = build_reader(get_reader(input)?, opts)?;
+            let headers = read_headers(&mut reader, opts)?;
+            (headers, Box::new(reader.into_records().map(|r| Ok(r?))))
+        };
+    let records = rows.map(|record| record_to_value(&headers, &record?, parser));
+    let records = transform_records(records, &headers, transform)?;
+    write_output(&write.output_path(), |writer| {
+        write_records(writer, records, write)
+    })
+}
+
+/// Serialize records one at a time, so memory use doesn't grow with the input.
+pub fn write_records<W, I>(writer: &mut W, records: I, opts: &CsvWriteOpts) -> Result<()>
+where
+    W: Write + Send,
+    I: Iterator<Item = Result<Value>>,
+{
+    match (opts.format, opts.stream) {
+        (OutputFormat::Json, false) => {
+            let mut ser = serde_json::Serializer::pretty(&mut *writer);
+            let mut seq = ser.serialize_seq(None)?;
+            for record in records {
+                seq.serialize_element(&record?)?;
+            }
+            seq.end()?;
+        }
+        (OutputFormat::Json, true) => {
+            for record in records {
+                serde_json::to_writer(&mut *writer, &record?)?;
+                writeln!(writer)?;
+            }
+        }
+        (OutputFormat::Yaml, false) => {
+            let mut ser = serde_yaml::Serializer::new(&mut *writer);
+            let mut seq = ser.serialize_seq(None)?;
+            for record in records {
+                seq.serialize_element(&record?)?;
+            }
+            seq.end()?;
+        }
+        (OutputFormat::Yaml, true) => {
+            let mut ser = serde_yaml::Serializer::new(&mut *writer);
+            for record in records {
+                record?.serialize(&mut ser)?;
+            }
+        }
+        (OutputFormat::Toml, _) => {
+            for (i, record) in records.enumerate() {
+                if i > 0 {
+                    writeln!(writer)?;
+                }
+                writer.write_all(to_toml_entry(&record?, &opts.table)?.as_bytes())?;
+            }
+        }
+        (OutputFormat::Csv | OutputFormat::Tsv, _) => {
+            let mut csv_writer = build_writer(&mut *writer, opts);
+            let mut columns: Option<Vec<String>> = None;
+            for (i, record) in records.enumerate() {
+                let Value::Object(map) = record? else {
+                    anyhow::bail!("csv output expects header-keyed records");
+                };
+                // the first record decides the header, which can't grow once it is written
+                let columns = match &mut columns {
+                    Some(columns) => columns,
+                    None => {
+                        csv_writer.write_record(map.keys())?;
+                        columns.insert(map.keys().cloned().collect())
+                    }
+                };
+                if let Some(key) = map.keys().find(|k| !columns.contains(k)) {
+                    anyhow::bail!(
+                        "record {} has column {:?}, which is not in the header of the first record",
+                        i + 1,
+                        key
+                    );
+                }
+                csv_writer.write_record(
+                    columns
+                        .iter()
+                        .map(|column| map.get(column).map(as_text).unwrap_or_default()),
+                )?;
+            }
+            csv_writer.flush()?;
+        }
+        (OutputFormat::Parquet | OutputFormat::Arrow, _) => {
+            write_columnar(&mut *writer, records, opts)?;
+        }
+    }
     Ok(())
 }
+
+/// CSV writer for the output dialect. TSV defaults to a tab delimiter.
+pub fn build_writer<W: Write>(wtr: W, opts: &CsvWriteOpts) -> csv::Writer<W> {
+    let delimiter = match (opts.out_delimiter, opts.format) {
+        (Some(delimiter), _) => delimiter,
+        (None, OutputFormat::Tsv) => b'\t',
+        (None, _) => b',',
+    };
+    let quote_style = match opts.quote_style {
+        CsvQuoteStyle::Always => QuoteStyle::Always,
+        CsvQuoteStyle::Necessary => QuoteStyle::Necessary,
+        CsvQuoteStyle::NonNumeric => QuoteStyle::NonNumeric,
+        CsvQuoteStyle::Never => QuoteStyle::Never,
+    };
+    let terminator = match opts.line_ending {
+        LineEnding::Lf => Terminator::Any(b'\n'),
+        LineEnding::Crlf => Terminator::CRLF,
+    };
+    WriterBuilder::new()
+        .delimiter(delimiter)
+        .quote_style(quote_style)
+        .terminator(terminator)
+        .from_writer(wtr)
+}
+
+/// CSV reader over `rdr`, transcoded to UTF-8 according to `--encoding`.
+pub fn build_reader<'a, R: Read + 'a>(
+    rdr: R,
+    opts: &CsvReadOpts,
+) -> Result<Reader<Box<dyn Read + 'a>>> {
+    let rdr = decode_reader(rdr, opts.encoding)?;
+    Ok(ReaderBuilder::new()
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .escape(opts.escape)
+        .double_quote(opts.escape.is_none())
+        .comment(opts.comment)
+        .flexible(opts.flexible)
+        .trim(if opts.trim { Trim::All } else { Trim::None })
+        .has_headers(opts.header)
+        .from_reader(rdr))
+}
+
+/// Header row of the file, or `col_0..col_n` when the file has no header.
+pub fn read_headers<R: Read>(reader: &mut Reader<R>, opts: &CsvReadOpts) -> Result<StringRecord> {
+    let headers = reader.headers()?;
+    if opts.header {
+        Ok(headers.clone())
+    } else {
+        Ok((0..headers.len()).map(|i| format!("col_{}", i)).collect())
+    }
+}
+
+/// Build a header-keyed JSON object from a record. Extra fields of flexible records get `col_n` keys.
+pub fn record_to_value(
+    headers: &StringRecord,
+    record: &StringRecord,
+    parser: &CellParser,
+) -> Result<Value> {
+    let mut obj = Map::with_capacity(record.len());
+    for (i, cell) in record.iter().enumerate() {
+        let header = match headers.get(i) {
+            Some(header) => header.to_string(),
+            None => format!("col_{}", i),
+        };
+        let value = parser.parse(&header, cell)?;
+        obj.insert(header, value);
+    }
+    Ok(Value::Object(obj))
+}
+
+/// TOML has no top-level arrays, so each record is written as one `[[table]]` entry.
+fn to_toml_entry(record: &Value, table: &str) -> Result<String> {
+    let entry = json_to_toml(record)?.into_iter().collect();
+    let mut doc = toml::Table::new();
+    doc.insert(table.to_string(), toml::Value::Array(entry));
+    Ok(toml::to_string(&doc)?)
+}
+
+/// Convert a JSON value into its TOML counterpart. TOML has no null, so nulls are dropped
+/// (`None`). Strings stay strings, only values the cell parser already typed get a TOML type.
+fn json_to_toml(value: &Value) -> Result<Option<toml::Value>> {
+    let value = match value {
+        Value::Null => return Ok(None),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => toml::Value::Integer(i),
+            (None, Some(f)) if f.is_finite() => toml::Value::Float(f),
+            _ => anyhow::bail!("{} is not a finite TOML number", n),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(arr) => toml::Value::Array(
+            arr.iter()
+                .filter_map(|v| json_to_toml(v).transpose())
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => toml::Value::Table(
+            map.iter()
+                .filter_map(|(k, v)| Some(Ok((k.clone(), json_to_toml(v).transpose()?.ok()?))))
+                .collect::<Result<_>>()?,
+        ),
+    };
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_semicolon_without_header() -> Result<()> {
+        let opts = CsvReadOpts {
+            delimiter: b';',
+            comment: Some(b'#'),
+            flexible: true,
+            trim: true,
+            header: false,
+            ..Default::default()
+        };
+        let data = "# exported from Excel\nSzczesny; 1\nPerin ;37; Italy\n";
+        let mut reader = build_reader(data.as_bytes(), &opts)?;
+        let headers = read_headers(&mut reader, &opts)?;
+        let parser = CellParser::default();
+        let records = reader
+            .records()
+            .map(|r| record_to_value(&headers, &r?, &parser))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(records[0], json!({"col_0": "Szczesny", "col_1": "1"}));
+        assert_eq!(
+            records[1],
+            json!({"col_0": "Perin", "col_1": "37", "col_2": "Italy"})
+        );
+        Ok(())
+    }
+
+    fn write_to_string(records: Vec<Value>, format: OutputFormat, stream: bool) -> String {
+        let opts = CsvWriteOpts {
+            format,
+            table: "player".to_string(),
+            stream,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        write_records(&mut buf, records.into_iter().map(Ok), &opts).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_write_records_json() {
+        let records = vec![json!({"Name": "Szczesny"}), json!({"Name": "Perin"})];
+        let content = write_to_string(records.clone(), OutputFormat::Json, false);
+        assert_eq!(content, serde_json::to_string_pretty(&records).unwrap());
+        let content = write_to_string(records, OutputFormat::Json, true);
+        assert_eq!(content, "{\"Name\":\"Szczesny\"}\n{\"Name\":\"Perin\"}\n");
+    }
+
+    #[test]
+    fn test_write_records_yaml() {
+        let records = vec![json!({"Name": "Szczesny"}), json!({"Name": "Perin"})];
+        let content = write_to_string(records.clone(), OutputFormat::Yaml, false);
+        assert_eq!(content, serde_yaml::to_string(&records).unwrap());
+        let content = write_to_string(records, OutputFormat::Yaml, true);
+        assert_eq!(content, "Name: Szczesny\n---\nName: Perin\n");
+    }
+
+    #[test]
+    fn test_write_records_csv() {
+        let records = vec![
+            json!({"Name": "Perin", "Kit Number": 37, "Captain": null}),
+            json!({"Kit Number": 1, "Name": "Szczesny, Wojciech", "Captain": true}),
+        ];
+        let content = write_to_string(records, OutputFormat::Csv, false);
+        assert_eq!(
+            content,
+            "Name,Kit Number,Captain\nPerin,37,\n\"Szczesny, Wojciech\",1,true\n"
+        );
+
+        let records = vec![
+            json!({"col_0": "Szczesny", "col_1": "1"}),
+            json!({"col_0": "Perin", "col_1": "37", "col_2": "Italy"}),
+        ];
+        let opts = CsvWriteOpts {
+            format: OutputFormat::Csv,
+            ..Default::default()
+        };
+        let ret = write_records(&mut Vec::new(), records.into_iter().map(Ok), &opts);
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn test_write_records_tsv_dialect() {
+        let records = vec![json!({"Name": "Perin", "Kit Number": 37})];
+        let opts = CsvWriteOpts {
+            format: OutputFormat::Tsv,
+            quote_style: CsvQuoteStyle::NonNumeric,
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        write_records(&mut buf, records.clone().into_iter().map(Ok), &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\"Name\"\t\"Kit Number\"\r\n\"Perin\"\t37\r\n"
+        );
+
+        let opts = CsvWriteOpts {
+            out_delimiter: Some(b';'),
+            quote_style: CsvQuoteStyle::Always,
+            ..opts
+        };
+        let mut buf = Vec::new();
+        write_records(&mut buf, records.into_iter().map(Ok), &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\"Name\";\"Kit Number\"\r\n\"Perin\";\"37\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_records_toml() -> Result<()> {
+        let parser = CellParser::new(true, crate::parse_types("zip=string")?);
+        let headers = StringRecord::from(vec!["zip", "name", "x"]);
+        let typed = record_to_value(
+            &headers,
+            &StringRecord::from(vec!["00501", "nan", "inf"]),
+            &parser,
+        )?;
+        let records = vec![
+            json!({"Name": "Wojciech Szczesny", "Kit Number": 1, "Captain": false}),
+            json!({"Name": "Mattia Perin", "Kit Number": "37", "Captain": null, "DOB": "1992-11-10"}),
+            typed,
+        ];
+        let content = write_to_string(records, OutputFormat::Toml, false);
+        let doc: toml::Table = content.parse()?;
+        let players = doc["player"].as_array().unwrap();
+        assert_eq!(players.len(), 3);
+        assert_eq!(players[0]["Kit Number"], toml::Value::Integer(1));
+        assert_eq!(players[0]["Captain"], toml::Value::Boolean(false));
+        assert_eq!(players[1]["Kit Number"], toml::Value::String("37".into()));
+        assert!(players[1].get("Captain").is_none());
+        assert_eq!(players[2]["zip"], toml::Value::String("00501".into()));
+        assert_eq!(players[2]["name"], toml::Value::String("nan".into()));
+        assert_eq!(players[2]["x"], toml::Value::String("inf".into()));
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{bail, Result};
+use csv::StringRecord;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Write},
+};
+
+use super::csv_filter::as_text;
+use crate::{
+    build_reader, get_reader, read_headers, record_to_value, CellParser, CsvReadOpts, DiffFormat,
+};
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct CsvDiff {
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<RowChange>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RowChange {
+    pub key: Map<String, Value>,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    key: &[String],
+    opts: &CsvReadOpts,
+    format: DiffFormat,
+) -> Result<()> {
+    let (old_headers, old_records) = read_keyed(old, key, opts)?;
+    let (new_headers, new_records) = read_keyed(new, key, opts)?;
+    let diff = diff_records(&old_headers, old_records, &new_headers, new_records, key);
+
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        DiffFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &diff)?;
+            writeln!(stdout)?;
+        }
+        DiffFormat::Text => {
+            let color = stdout.is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            stdout.write_all(render_diff(&diff, color).as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+type KeyedRecords = Vec<(Vec<String>, Map<String, Value>)>;
+
+fn read_keyed(
+    input: &str,
+    key: &[String],
+    opts: &CsvReadOpts,
+) -> Result<(StringRecord, KeyedRecords)> {
+    let mut reader = build_reader(get_reader(input)?, opts)?;
+    let headers = read_headers(&mut reader, opts)?;
+    for column in key {
+        if !headers.iter().any(|h| h == column) {
+            bail!("key column {:?} not found in {}", column, input);
+        }
+    }
+    let parser = CellParser::default();
+    let mut seen = HashMap::new();
+    let mut records = Vec::new();
+    for (i, result) in reader.records().enumerate() {
+        let Value::Object(record) = record_to_value(&headers, &result?, &parser)? else {
+            unreachable!("record_to_value builds objects");
+        };
+        let id: Vec<String> = key.iter().map(|k| as_text(cell(&record, k))).collect();
+        if let Some(first) = seen.insert(id.clone(), i) {
+            bail!(
+                "duplicate key {:?} in {}, records {} and {}",
+                id,
+                input,
+                first + 1,
+                i + 1
+            );
+        }
+        records.push((id, record));
+    }
+    Ok((headers, records))
+}
+
+/// Match records by key. Cells are compared as text, only in columns both files have.
+pub fn diff_records(
+    old_headers: &StringRecord,
+    old: KeyedRecords,
+    new_headers: &StringRecord,
+    new: KeyedRecords,
+    key: &[String],
+) -> CsvDiff {
+    let missing_from = |headers: &StringRecord, other: &StringRecord| -> Vec<String> {
+        headers
+            .iter()
+            .filter(|h| !other.iter().any(|o| o == *h))
+            .map(String::from)
+            .collect()
+    };
+    let mut diff = CsvDiff {
+        added_columns: missing_from(new_headers, old_headers),
+        removed_columns: missing_from(old_headers, new_headers),
+        ..Default::default()
+    };
+    let common: Vec<&str> = old_headers
+        .iter()
+        .filter(|h| new_headers.iter().any(|n| n == *h) && !key.iter().any(|k| k == h))
+        .collect();
+
+    let mut new_index: HashMap<Vec<String>, Map<String, Value>> = HashMap::new();
+    let mut new_order = Vec::with_capacity(new.len());
+    for (id, record) in new {
+        new_order.push(id.clone());
+        new_index.insert(id, record);
+    }
+
+    for (id, old_record) in old {
+        let Some(new_record) = new_index.remove(&id) else {
+            diff.removed.push(Value::Object(old_record));
+            continue;
+        };
+        let changes: Vec<CellChange> = common
+            .iter()
+            .filter_map(|&column| {
+                let old = as_text(cell(&old_record, column));
+                let new = as_text(cell(&new_record, column));
+                (old != new).then(|| CellChange {
+                    column: column.to_string(),
+                    old,
+                    new,
+                })
+            })
+            .collect();
+        if !changes.is_empty() {
+            let key = key
+                .iter()
+                .map(|k| (k.clone(), cell(&old_record, k).clone()))
+                .collect();
+            diff.changed.push(RowChange { key, changes });
+        }
+    }
+    for id in new_order {
+        if let Some(record) = new_index.remove(&id) {
+            diff.added.push(Value::Object(record));
+        }
+    }
+    diff
+}
+
+// short rows of a flexible file lack their last columns
+fn cell<'a>(record: &'a Map<String, Value>, column: &str) -> &'a Value {
+    record.get(column).unwrap_or(&Value::Null)
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+pub fn render_diff(diff: &CsvDiff, color: bool) -> String {
+    let paint = |s: String, code: &str| {
+        if color {
+            format!("{}{}{}", code, s, RESET)
+        } else {
+            s
+        }
+    };
+    let describe = |record: &Value| -> String {
+        record
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| format!("{}={}", k, as_text(v)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut out = String::new();
+    for column in &diff.added_columns {
+        out.push_str(&paint(format!("+ column {}", column), GREEN));
+        out.push('\n');
+    }
+    for column in &diff.removed_columns {
+        out.push_str(&paint(format!("- column {}", column), RED));
+        out.push('\n');
+    }
+    for record in &diff.removed {
+        out.push_str(&paint(format!("- {}", describe(record)), RED));
+        out.push('\n');
+    }
+    for record in &diff.added {
+        out.push_str(&paint(format!("+ {}", describe(record)), GREEN));
+        out.push('\n');
+    }
+    for row in &diff.changed {
+        out.push_str(&paint(
+            format!("~ {}", describe(&Value::Object(row.key.clone()))),
+            YELLOW,
+        ));
+        out.push('\n');
+        for change in &row.changes {
+            out.push_str(&format!(
+                "    {}: {} -> {}\n",
+                change.column,
+                paint(format!("{:?}", change.old), RED),
+                paint(format!("{:?}", change.new), GREEN)
+            ));
+        }
+    }
+    out.push_str(&format!(
+        "{} added, {} removed, {} changed\n",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keyed(records: Vec<Value>) -> KeyedRecords {
+        records
+            .into_iter()
+            .map(|r| match r {
+                Value::Object(map) => (vec![as_text(&map["id"])], map),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_records() {
+        let old_headers = StringRecord::from(vec!["id", "Name", "Age"]);
+        let new_headers = StringRecord::from(vec!["id", "Name", "Club"]);
+        let old = keyed(vec![
+            json!({"id": "1", "Name": "Buffon", "Age": "45"}),
+            json!({"id": "2", "Name": "Perin", "Age": "31"}),
+        ]);
+        let new = keyed(vec![
+            json!({"id": "3", "Name": "Chiellini", "Club": "LAFC"}),
+            json!({"id": "1", "Name": "Gianluigi Buffon", "Club": "Juventus"}),
+        ]);
+        let diff = diff_records(&old_headers, old, &new_headers, new, &["id".to_string()]);
+        assert_eq!(diff.added_columns, vec!["Club"]);
+        assert_eq!(diff.removed_columns, vec!["Age"]);
+        assert_eq!(
+            diff.added,
+            vec![json!({"id": "3", "Name": "Chiellini", "Club": "LAFC"})]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![json!({"id": "2", "Name": "Perin", "Age": "31"})]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![RowChange {
+                key: json!({"id": "1"}).as_object().unwrap().clone(),
+                changes: vec![CellChange {
+                    column: "Name".into(),
+                    old: "Buffon".into(),
+                    new: "Gianluigi Buffon".into(),
+                }],
+            }]
+        );
+        assert_eq!(
+            render_diff(&diff, false),
+            "\
++ column Club
+- column Age
+- id=2, Name=Perin, Age=31
++ id=3, Name=Chiellini, Club=LAFC
+~ id=1
+    Name: \"Buffon\" -> \"Gianluigi Buffon\"
+1 added, 1 removed, 1 changed
+"
+        );
+    }
+
+    #[test]
+    fn test_diff_records_short_row() {
+        let headers = StringRecord::from(vec!["id", "Name", "Age"]);
+        let old = keyed(vec![json!({"id": "1", "Name": "Buffon", "Age": "45"})]);
+        let new = keyed(vec![json!({"id": "1", "Name": "Buffon"})]);
+        let diff = diff_records(&headers, old, &headers, new, &["id".to_string()]);
+        assert_eq!(
+            diff.changed,
+            vec![RowChange {
+                key: json!({"id": "1"}).as_object().unwrap().clone(),
+                changes: vec![CellChange {
+                    column: "Age".into(),
+                    old: "45".into(),
+                    new: "".into(),
+                }],
+            }]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{Cursor, Read};
+
+use crate::InputEncoding;
+
+// enough bytes to make a confident guess without buffering the whole file
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Wrap a reader so it yields UTF-8, transcoding from the given or detected encoding.
+/// A leading BOM is always stripped.
+pub fn decode_reader<'a, R: Read + 'a>(
+    mut rdr: R,
+    encoding: InputEncoding,
+) -> Result<Box<dyn Read + 'a>> {
+    let mut builder = DecodeReaderBytesBuilder::new();
+    builder.strip_bom(true);
+    let encoding = match encoding {
+        InputEncoding::Label(encoding) => encoding,
+        InputEncoding::Auto => {
+            let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+            rdr.by_ref()
+                .take(SAMPLE_SIZE as u64)
+                .read_to_end(&mut sample)?;
+            let is_last = sample.len() < SAMPLE_SIZE;
+            let encoding = detect_encoding(&sample, is_last);
+            let rdr = Cursor::new(sample).chain(rdr);
+            return Ok(match encoding {
+                // a BOM takes precedence, let the decoder sniff it
+                None => Box::new(builder.build(rdr)),
+                Some(encoding) if encoding == UTF_8 => {
+                    Box::new(builder.utf8_passthru(true).build(rdr))
+                }
+                Some(encoding) => Box::new(builder.encoding(Some(encoding)).build(rdr)),
+            });
+        }
+    };
+    Ok(Box::new(builder.encoding(Some(encoding)).build(rdr)))
+}
+
+/// Guess the encoding of the start of a file. `None` means the sample starts with a BOM.
+pub fn detect_encoding(sample: &[u8], is_last: bool) -> Option<&'static Encoding> {
+    if Encoding::for_bom(sample).is_some() {
+        return None;
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return Some(encoding);
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return Some(UTF_8),
+        // the sample may end in the middle of a multi-byte character
+        Err(e) if e.error_len().is_none() && !is_last => return Some(UTF_8),
+        Err(_) => {}
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, is_last);
+    Some(detector.guess(None, true))
+}
+
+// UTF-16 without a BOM: mostly-ASCII text has a zero in every other byte
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    if odd * 10 > pairs * 4 && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 10 > pairs * 4 && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, SHIFT_JIS};
+
+    fn decode(bytes: &[u8], encoding: InputEncoding) -> String {
+        let mut out = String::new();
+        decode_reader(bytes, encoding)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_decode_reader_auto() {
+        let text = "Name,Nationality\n布冯,意大利\n";
+        let (gbk, _, _) = GBK.encode(text);
+        assert_eq!(decode(&gbk, InputEncoding::Auto), text);
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(text.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        assert_eq!(decode(&utf16, InputEncoding::Auto), text);
+
+        let utf16: Vec<u8> = "Name,Kit\nPerin,37\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert_eq!(decode(&utf16, InputEncoding::Auto), "Name,Kit\nPerin,37\n");
+
+        let bom = format!("\u{feff}{}", text);
+        assert_eq!(decode(bom.as_bytes(), InputEncoding::Auto), text);
+    }
+
+    #[test]
+    fn test_decode_reader_label() {
+        let text = "名前,背番号\n";
+        let (sjis, _, _) = SHIFT_JIS.encode(text);
+        assert_eq!(decode(&sjis, InputEncoding::Label(SHIFT_JIS)), text);
+    }
+}
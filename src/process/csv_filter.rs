@@ -0,0 +1,354 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+/// A `--where` expression, e.g. `Position == "Goalkeeper" and `Kit Number` < 30`.
+///
+/// Operands are column names (backquote names that contain spaces), string or number literals,
+/// `true`, `false` and `null`. Comparisons are numeric when both sides look like numbers.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Operand, CompareOp, Operand),
+    Match(Operand, Regex, bool),
+}
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Column(String),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Column(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+pub fn parse_filter(expr: &str) -> Result<Filter> {
+    let tokens = tokenize(expr)?;
+    let mut parser = FilterParser { tokens, pos: 0 };
+    let filter = parser.parse_or()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        bail!("unexpected {:?} in filter {:?}", token, expr);
+    }
+    Ok(filter)
+}
+
+impl Filter {
+    /// Columns the expression refers to, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Filter::And(l, r) | Filter::Or(l, r) => {
+                let mut columns = l.columns();
+                columns.extend(r.columns());
+                columns
+            }
+            Filter::Not(f) => f.columns(),
+            Filter::Compare(l, _, r) => l.column().into_iter().chain(r.column()).collect(),
+            Filter::Match(o, _, _) => o.column().into_iter().collect(),
+        }
+    }
+
+    pub fn matches(&self, record: &Map<String, Value>) -> bool {
+        match self {
+            Filter::And(l, r) => l.matches(record) && r.matches(record),
+            Filter::Or(l, r) => l.matches(record) || r.matches(record),
+            Filter::Not(f) => !f.matches(record),
+            Filter::Compare(l, op, r) => compare(l.resolve(record), *op, r.resolve(record)),
+            Filter::Match(operand, re, negate) => {
+                let matched = match operand.resolve(record) {
+                    Value::Null => false,
+                    Value::String(s) => re.is_match(s),
+                    v => re.is_match(&v.to_string()),
+                };
+                matched != *negate
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn column(&self) -> Option<&str> {
+        match self {
+            Operand::Column(name) => Some(name),
+            Operand::Literal(_) => None,
+        }
+    }
+
+    fn resolve<'a>(&'a self, record: &'a Map<String, Value>) -> &'a Value {
+        match self {
+            Operand::Column(name) => record.get(name).unwrap_or(&Value::Null),
+            Operand::Literal(v) => v,
+        }
+    }
+}
+
+fn compare(l: &Value, op: CompareOp, r: &Value) -> bool {
+    let ordering = match (l, r) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Value::Null) => None,
+        _ => match (as_number(l), as_number(r)) {
+            (Some(l), Some(r)) => l.partial_cmp(&r),
+            _ => Some(as_text(l).cmp(&as_text(r))),
+        },
+    };
+    match (op, ordering) {
+        (CompareOp::Ne, ordering) => ordering != Some(Ordering::Equal),
+        (_, None) => false,
+        (CompareOp::Eq, Some(o)) => o == Ordering::Equal,
+        (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+        (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+        (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+        (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+    }
+}
+
+pub fn as_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok().filter(|f: &f64| f.is_finite()),
+        _ => None,
+    }
+}
+
+pub fn as_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+struct FilterParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl FilterParser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Ident(s)) if s.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("or") {
+            left = Filter::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("and") {
+            left = Filter::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter> {
+        if self.eat_keyword("not") {
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+        if self.tokens.get(self.pos) == Some(&Token::LParen) {
+            self.pos += 1;
+            let filter = self.parse_or()?;
+            if self.next() != Some(Token::RParen) {
+                bail!("missing closing parenthesis");
+            }
+            return Ok(filter);
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Filter> {
+        let left = self.parse_operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            t => bail!("expect a comparison operator, got {:?}", t),
+        };
+        if op == "=~" || op == "!~" {
+            let pattern = match self.next() {
+                Some(Token::Str(s)) => s,
+                t => bail!("expect a regex string after {}, got {:?}", op, t),
+            };
+            return Ok(Filter::Match(left, Regex::new(&pattern)?, op == "!~"));
+        }
+        let op = match op {
+            "==" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            _ => CompareOp::Ge,
+        };
+        Ok(Filter::Compare(left, op, self.parse_operand()?))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand> {
+        let operand = match self.next() {
+            Some(Token::Str(s)) => Operand::Literal(Value::String(s)),
+            Some(Token::Num(n)) => Operand::Literal(n.into()),
+            Some(Token::Column(s)) => Operand::Column(s),
+            Some(Token::Ident(s)) => match s.as_str() {
+                "true" => Operand::Literal(Value::Bool(true)),
+                "false" => Operand::Literal(Value::Bool(false)),
+                "null" => Operand::Literal(Value::Null),
+                _ => Operand::Column(s),
+            },
+            t => bail!("expect a column or a literal, got {:?}", t),
+        };
+        Ok(operand)
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' => {
+                chars.next();
+                if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                }
+            }
+            '"' | '\'' => Token::Str(read_quoted(&mut chars)?),
+            '`' => Token::Column(read_quoted(&mut chars)?),
+            '=' | '!' | '<' | '>' => read_op(&mut chars)?,
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let s = read_while(&mut chars, |c| {
+                    c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'
+                });
+                Token::Num(s.parse().map_err(|_| anyhow!("invalid number {:?}", s))?)
+            }
+            c if c.is_alphabetic() || c == '_' => Token::Ident(read_while(&mut chars, |c| {
+                c.is_alphanumeric() || c == '_' || c == '.'
+            })),
+            c => bail!("unexpected character {:?} in filter", c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn read_while(chars: &mut Peekable<Chars>, f: impl Fn(char) -> bool) -> String {
+    let mut s = String::new();
+    while let Some(&c) = chars.peek() {
+        if !f(c) {
+            break;
+        }
+        s.push(c);
+        chars.next();
+    }
+    s
+}
+
+// a quoted string or column name; the quote can be escaped with a backslash
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let quote = chars.next().unwrap_or_default();
+    let mut s = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&quote) => s.extend(chars.next()),
+            c if c == quote => return Ok(s),
+            c => s.push(c),
+        }
+    }
+    bail!("unterminated {} quote", quote)
+}
+
+fn read_op(chars: &mut Peekable<Chars>) -> Result<Token> {
+    const OPS: [&str; 8] = ["==", "!=", "<=", ">=", "=~", "!~", "<", ">"];
+    let first = chars.next().unwrap_or_default();
+    let second = chars.peek().copied();
+    for op in OPS {
+        let mut op_chars = op.chars();
+        if op_chars.next() != Some(first) {
+            continue;
+        }
+        match op_chars.next() {
+            Some(c) if Some(c) == second => {
+                chars.next();
+                return Ok(Token::Op(op));
+            }
+            None => return Ok(Token::Op(op)),
+            _ => {}
+        }
+    }
+    bail!("unknown operator starting with {:?}", first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record() -> Map<String, Value> {
+        match json!({"Name": "Gianluigi Buffon", "Position": "Goalkeeper", "Kit Number": "77"}) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_filter_matches() -> Result<()> {
+        let record = record();
+        assert!(parse_filter(r#"Position == "Goalkeeper""#)?.matches(&record));
+        assert!(parse_filter("`Kit Number` > 9")?.matches(&record));
+        assert!(!parse_filter("`Kit Number` <= 9.5")?.matches(&record));
+        assert!(
+            parse_filter(r#"Name =~ '^Gian' and not Position != "Goalkeeper""#)?.matches(&record)
+        );
+        assert!(
+            parse_filter(r#"Name !~ "Perin" and (DOB == null or DOB > "1990")"#)?.matches(&record)
+        );
+        assert!(!parse_filter(r#"Position == "Defender" or Name == "Perin""#)?.matches(&record));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_filter_errors() {
+        assert!(parse_filter("Position =").is_err());
+        assert!(parse_filter(r#"Position == "Goalkeeper"#).is_err());
+        assert!(parse_filter("(Position == 1").is_err());
+        assert!(parse_filter("Name =~ '('").is_err());
+        assert!(parse_filter("Name = 1").is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::{bail, Result};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::csv_filter::as_text;
+use crate::{
+    build_reader, get_reader, read_headers, record_to_value, write_output, write_records,
+    CellParser, CsvReadOpts, CsvWriteOpts, JoinType,
+};
+
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    on: &[String],
+    how: JoinType,
+    opts: &CsvReadOpts,
+    parser: &CellParser,
+    write: &CsvWriteOpts,
+) -> Result<()> {
+    let mut reader = build_reader(get_reader(right)?, opts)?;
+    let right_headers = read_headers(&mut reader, opts)?;
+    let right_records = reader
+        .into_records()
+        .map(|record| record_to_value(&right_headers, &record?, parser))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut reader = build_reader(get_reader(left)?, opts)?;
+    let left_headers = read_headers(&mut reader, opts)?;
+    let left_records = reader
+        .into_records()
+        .map(|record| record_to_value(&left_headers, &record?, parser));
+
+    let records = join_records(
+        left_records,
+        &left_headers,
+        right_records,
+        &right_headers,
+        on,
+        how,
+    )?;
+    write_output(&write.output_path(), |writer| {
+        write_records(writer, records, write)
+    })
+}
+
+pub fn process_csv_concat(
+    inputs: &[String],
+    opts: &CsvReadOpts,
+    parser: &CellParser,
+    write: &CsvWriteOpts,
+) -> Result<()> {
+    let mut readers = Vec::with_capacity(inputs.len());
+    let mut columns: Vec<String> = Vec::new();
+    for input in inputs {
+        let mut reader = build_reader(get_reader(input)?, opts)?;
+        let headers = read_headers(&mut reader, opts)?;
+        for header in headers.iter() {
+            if !columns.iter().any(|c| c == header) {
+                columns.push(header.to_string());
+            }
+        }
+        readers.push((reader, headers));
+    }
+
+    let records = readers.into_iter().flat_map(|(reader, headers)| {
+        reader
+            .into_records()
+            .map(move |record| record_to_value(&headers, &record?, parser))
+    });
+    let records = concat_records(records, &columns);
+    write_output(&write.output_path(), |writer| {
+        write_records(writer, records, write)
+    })
+}
+
+/// Give every record the union of all columns, in order of first appearance.
+/// Columns a file doesn't have are null.
+pub fn concat_records<'a, I>(
+    records: I,
+    columns: &'a [String],
+) -> impl Iterator<Item = Result<Value>> + 'a
+where
+    I: Iterator<Item = Result<Value>> + 'a,
+{
+    records.map(move |record| {
+        let Value::Object(mut map) = record? else {
+            bail!("expect a header-keyed record");
+        };
+        Ok(Value::Object(
+            columns
+                .iter()
+                .map(|column| (column.clone(), map.remove(column).unwrap_or_default()))
+                .collect(),
+        ))
+    })
+}
+
+/// Hash join: the right records are indexed by key, the left ones are streamed.
+///
+/// Output columns are the left columns followed by the right ones without the key columns.
+/// Right columns whose name is taken get a `_right` suffix, then `_right_2` and so on until the
+/// name is free. Unmatched sides are null. As with NULL in SQL, a record with an empty or null
+/// key matches nothing.
+pub fn join_records<'a, I>(
+    left: I,
+    left_headers: &StringRecord,
+    right: Vec<Value>,
+    right_headers: &StringRecord,
+    on: &[String],
+    how: JoinType,
+) -> Result<Box<dyn Iterator<Item = Result<Value>> + 'a>>
+where
+    I: Iterator<Item = Result<Value>> + 'a,
+{
+    for column in on {
+        if !left_headers.iter().any(|h| h == column) {
+            bail!("join column {:?} not found in the left file", column);
+        }
+        if !right_headers.iter().any(|h| h == column) {
+            bail!("join column {:?} not found in the right file", column);
+        }
+    }
+
+    let left_columns: Vec<String> = left_headers.iter().map(String::from).collect();
+    let mut taken: HashSet<String> = left_headers
+        .iter()
+        .chain(right_headers.iter())
+        .map(String::from)
+        .collect();
+    let mut right_columns = Vec::new();
+    for h in right_headers.iter().filter(|h| !on.iter().any(|c| c == h)) {
+        let mut name = h.to_string();
+        if left_headers.iter().any(|l| l == h) {
+            name = format!("{}_right", h);
+            let mut n = 1;
+            while taken.contains(&name) {
+                n += 1;
+                name = format!("{}_right_{}", h, n);
+            }
+        }
+        taken.insert(name.clone());
+        right_columns.push((h.to_string(), name));
+    }
+
+    let right: Vec<Map<String, Value>> = right
+        .into_iter()
+        .filter_map(|record| match record {
+            Value::Object(map) => Some(map),
+            _ => None,
+        })
+        .collect();
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, record) in right.iter().enumerate() {
+        if let Some(key) = join_key(record, on) {
+            index.entry(key).or_default().push(i);
+        }
+    }
+
+    Ok(Box::new(JoinIter {
+        left,
+        on: on.to_vec(),
+        how,
+        left_columns,
+        right_columns,
+        matched: vec![false; right.len()],
+        right,
+        index,
+        pending: VecDeque::new(),
+        left_done: false,
+        next_right: 0,
+    }))
+}
+
+// `None` when a key column is empty or null
+fn join_key(record: &Map<String, Value>, on: &[String]) -> Option<Vec<String>> {
+    on.iter()
+        .map(|column| {
+            let text = record.get(column).map(as_text).unwrap_or_default();
+            (!text.is_empty()).then_some(text)
+        })
+        .collect()
+}
+
+struct JoinIter<I> {
+    left: I,
+    on: Vec<String>,
+    how: JoinType,
+    left_columns: Vec<String>,
+    // (column in the right file, column in the output)
+    right_columns: Vec<(String, String)>,
+    right: Vec<Map<String, Value>>,
+    index: HashMap<Vec<String>, Vec<usize>>,
+    matched: Vec<bool>,
+    pending: VecDeque<Value>,
+    left_done: bool,
+    next_right: usize,
+}
+
+impl<I> JoinIter<I> {
+    fn merge(
+        &self,
+        left: Option<&Map<String, Value>>,
+        right: Option<&Map<String, Value>>,
+    ) -> Value {
+        let mut map = Map::with_capacity(self.left_columns.len() + self.right_columns.len());
+        for column in &self.left_columns {
+            let value = match (left, right) {
+                (Some(left), _) => left.get(column),
+                // keys of unmatched right records come from the right file
+                (None, Some(right)) if self.on.contains(column) => right.get(column),
+                _ => None,
+            };
+            map.insert(column.clone(), value.cloned().unwrap_or_default());
+        }
+        for (source, name) in &self.right_columns {
+            let value = right.and_then(|right| right.get(source)).cloned();
+            map.insert(name.clone(), value.unwrap_or_default());
+        }
+        Value::Object(map)
+    }
+
+    fn probe(&mut self, left: Map<String, Value>) {
+        let matches = join_key(&left, &self.on)
+            .and_then(|key| self.index.get(&key).cloned())
+            .unwrap_or_default();
+        if matches.is_empty() && self.how != JoinType::Inner {
+            let record = self.merge(Some(&left), None);
+            self.pending.push_back(record);
+        }
+        for i in matches {
+            self.matched[i] = true;
+            let record = self.merge(Some(&left), Some(&self.right[i]));
+            self.pending.push_back(record);
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Value>>> Iterator for JoinIter<I> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            if self.left_done {
+                break;
+            }
+            match self.left.next() {
+                Some(Ok(Value::Object(map))) => self.probe(map),
+                Some(Ok(_)) => return Some(Err(anyhow::anyhow!("expect a header-keyed record"))),
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.left_done = true,
+            }
+        }
+        if self.how != JoinType::Outer {
+            return None;
+        }
+        while self.next_right < self.right.len() {
+            let i = self.next_right;
+            self.next_right += 1;
+            if !self.matched[i] {
+                return Some(Ok(self.merge(None, Some(&self.right[i]))));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn join(how: JoinType) -> Result<Vec<Value>> {
+        let left_headers = StringRecord::from(vec!["id", "Name"]);
+        let right_headers = StringRecord::from(vec!["id", "Name", "Club"]);
+        let left = vec![
+            json!({"id": "1", "Name": "Buffon"}),
+            json!({"id": "2", "Name": "Perin"}),
+        ];
+        let right = vec![
+            json!({"id": "1", "Name": "Gianluigi Buffon", "Club": "Juventus"}),
+            json!({"id": "3", "Name": "Chiellini", "Club": "LAFC"}),
+        ];
+        join_records(
+            left.into_iter().map(Ok),
+            &left_headers,
+            right,
+            &right_headers,
+            &["id".to_string()],
+            how,
+        )?
+        .collect()
+    }
+
+    #[test]
+    fn test_join_records() -> Result<()> {
+        let buffon = json!({"id": "1", "Name": "Buffon", "Name_right": "Gianluigi Buffon", "Club": "Juventus"});
+        let perin = json!({"id": "2", "Name": "Perin", "Name_right": null, "Club": null});
+        let chiellini = json!({"id": "3", "Name": null, "Name_right": "Chiellini", "Club": "LAFC"});
+        assert_eq!(join(JoinType::Inner)?, vec![buffon.clone()]);
+        assert_eq!(join(JoinType::Left)?, vec![buffon.clone(), perin.clone()]);
+        assert_eq!(join(JoinType::Outer)?, vec![buffon, perin, chiellini]);
+
+        let headers = StringRecord::from(vec!["id"]);
+        let ret = join_records(
+            std::iter::empty(),
+            &headers,
+            vec![],
+            &headers,
+            &["Name".to_string()],
+            JoinType::Inner,
+        );
+        assert!(ret.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_records_names_and_empty_keys() -> Result<()> {
+        let left_headers = StringRecord::from(vec!["id", "Name", "Name_right"]);
+        let right_headers = StringRecord::from(vec!["id", "Name", "Name_right_2"]);
+        let left = vec![
+            json!({"id": "1", "Name": "Buffon", "Name_right": "a"}),
+            json!({"id": "", "Name": "Perin", "Name_right": "b"}),
+        ];
+        let right = vec![
+            json!({"id": "1", "Name": "Gianluigi Buffon", "Name_right_2": "c"}),
+            json!({"id": null, "Name": "Chiellini", "Name_right_2": "d"}),
+        ];
+        let ret: Vec<Value> = join_records(
+            left.into_iter().map(Ok),
+            &left_headers,
+            right,
+            &right_headers,
+            &["id".to_string()],
+            JoinType::Outer,
+        )?
+        .collect::<Result<_>>()?;
+        let keys: Vec<_> = ret[0].as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            vec!["id", "Name", "Name_right", "Name_right_3", "Name_right_2"]
+        );
+        assert_eq!(ret[0]["Name_right_3"], "Gianluigi Buffon");
+        // empty keys match nothing, not each other
+        assert_eq!(ret.len(), 3);
+        assert_eq!(ret[1]["Name_right_3"], Value::Null);
+        assert_eq!(ret[2]["Name_right_3"], "Chiellini");
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat_records() -> Result<()> {
+        let columns = vec!["Name".to_string(), "Club".to_string(), "Age".to_string()];
+        let records = vec![
+            json!({"Name": "Buffon", "Club": "Juventus"}),
+            json!({"Age": "32", "Name": "Perin"}),
+        ];
+        let ret =
+            concat_records(records.into_iter().map(Ok), &columns).collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            ret,
+            vec![
+                json!({"Name": "Buffon", "Club": "Juventus", "Age": null}),
+                json!({"Name": "Perin", "Club": null, "Age": "32"}),
+            ]
+        );
+        let keys: Vec<_> = ret[1].as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["Name", "Club", "Age"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use super::{csv_filter::as_text, text::Blake3};
+use crate::MaskStrategy;
+
+const FIRST_NAMES: [&str; 24] = [
+    "Alice", "Bruno", "Chiara", "Dario", "Elena", "Fabio", "Giulia", "Hugo", "Irene", "Jonas",
+    "Katia", "Luca", "Marta", "Nico", "Olga", "Paolo", "Rita", "Sandro", "Tina", "Ugo", "Vera",
+    "Walter", "Ylenia", "Zeno",
+];
+const LAST_NAMES: [&str; 24] = [
+    "Abate",
+    "Bianchi",
+    "Colombo",
+    "De Luca",
+    "Esposito",
+    "Ferri",
+    "Galli",
+    "Hofer",
+    "Innocenti",
+    "Lombardi",
+    "Marino",
+    "Neri",
+    "Orlando",
+    "Pellegrini",
+    "Quaranta",
+    "Rizzo",
+    "Santoro",
+    "Testa",
+    "Urso",
+    "Valentini",
+    "Villa",
+    "Zanetti",
+    "Moretti",
+    "Costa",
+];
+
+/// Masks cells column by column. Hashing and fakes are keyed with a BLAKE3 key, the same kind
+/// `text generate` writes, and depend only on the cell, so a value masks the same way in every
+/// file and column that uses the same key.
+pub struct Masker<'a> {
+    columns: &'a [(String, MaskStrategy)],
+    key: Option<Blake3>,
+}
+
+impl<'a> Masker<'a> {
+    pub fn new(columns: &'a [(String, MaskStrategy)], key: Option<&[u8]>) -> Result<Self> {
+        let keyed = columns.iter().any(|(_, s)| *s != MaskStrategy::Redact);
+        let key = match key {
+            Some(key) if key.len() < 32 => bail!("mask key must be at least 32 bytes"),
+            Some(key) => Some(Blake3::try_new(key)?),
+            None if keyed => bail!("--mask with hash or fake needs a --mask-key"),
+            None => None,
+        };
+        Ok(Self { columns, key })
+    }
+
+    pub fn mask(&self, record: Value) -> Value {
+        let Value::Object(mut map) = record else {
+            return record;
+        };
+        for (column, strategy) in self.columns {
+            if let Some(value) = map.get_mut(column) {
+                let text = as_text(value);
+                if !text.is_empty() {
+                    *value = Value::String(self.mask_text(&text, *strategy));
+                }
+            }
+        }
+        Value::Object(map)
+    }
+
+    fn mask_text(&self, text: &str, strategy: MaskStrategy) -> String {
+        match strategy {
+            MaskStrategy::Redact => redact(text),
+            MaskStrategy::Hash => hex::encode(self.digest(text, 32)),
+            MaskStrategy::Fake => self.fake(text),
+        }
+    }
+
+    fn digest(&self, text: &str, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        let key = self.key.as_ref().expect("checked in Masker::new");
+        key.keyed_xof(text.as_bytes()).fill(&mut buf);
+        buf
+    }
+
+    /// Emails become name-like addresses at example.com, values without letters keep their layout
+    /// with every digit replaced, anything else becomes a name with the same number of words.
+    ///
+    /// Names and emails carry a 48-bit suffix from the digest, so two distinct values only get the
+    /// same fake with a chance below n² / 2⁵⁷ among n values. Digit-only values stay within
+    /// their own length and can collide once a column holds a sizeable share of that space.
+    fn fake(&self, text: &str) -> String {
+        let pick = |bytes: &[u8], names: &[&'static str]| {
+            names[u16::from_le_bytes([bytes[0], bytes[1]]) as usize % names.len()]
+        };
+        if text.contains('@') {
+            let digest = self.digest(text, 10);
+            let local = format!(
+                "{}.{}.{}",
+                pick(&digest[0..2], &FIRST_NAMES),
+                pick(&digest[2..4], &LAST_NAMES),
+                hex::encode(&digest[4..10])
+            );
+            return format!("{}@example.com", local.to_lowercase().replace(' ', ""));
+        }
+        if !text.chars().any(char::is_alphabetic) {
+            let mut digest = self.digest(text, text.len()).into_iter();
+            return text
+                .chars()
+                .map(|c| match (c.is_ascii_digit(), digest.next()) {
+                    (true, Some(b)) => char::from(b'0' + b % 10),
+                    _ => c,
+                })
+                .collect();
+        }
+        let words = text.split_whitespace().count().max(1);
+        let digest = self.digest(text, words * 2 + 6);
+        let (names, suffix) = digest.split_at(words * 2);
+        let name = names
+            .chunks(2)
+            .enumerate()
+            .map(|(i, bytes)| match i {
+                0 => pick(bytes, &FIRST_NAMES),
+                _ => pick(bytes, &LAST_NAMES),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{}-{}", name, hex::encode(suffix))
+    }
+}
+
+/// Letters become x or X and digits become #, everything else is kept.
+fn redact(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            c if c.is_numeric() => '#',
+            c if c.is_uppercase() => 'X',
+            c if c.is_alphabetic() => 'x',
+            c => c,
+        })
+        .collect()
+}
+
+pub fn parse_mask(value: &str) -> Result<(String, MaskStrategy)> {
+    let (column, strategy) = value
+        .rsplit_once('=')
+        .ok_or_else(|| anyhow!("invalid mask {:?}, expect column=strategy", value))?;
+    Ok((column.to_string(), strategy.parse()?))
+}
+
+/// Keys a masker from `--mask-key`, a file such as the one `text generate` writes.
+pub fn load_masker<'a>(
+    columns: &'a [(String, MaskStrategy)],
+    key: Option<&str>,
+) -> Result<Masker<'a>> {
+    let key = key.map(std::fs::read).transpose()?;
+    Masker::new(columns, key.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: &[u8] = include_bytes!("../../fixtures/blake3.txt");
+
+    #[test]
+    fn test_mask_records() -> Result<()> {
+        let columns = vec![
+            parse_mask("email=hash")?,
+            parse_mask("phone=redact")?,
+            parse_mask("name=fake")?,
+            parse_mask("contact=fake")?,
+            parse_mask("kit=fake")?,
+        ];
+        let masker = Masker::new(&columns, Some(KEY))?;
+        let record = json!({"name": "Gianluigi Buffon", "email": "gigi@juventus.com", "phone": "+39 011-6563",
+            "contact": "gigi@juventus.com", "kit": 77, "club": "Juventus"});
+        let masked = masker.mask(record.clone());
+
+        assert_eq!(masked["phone"], "+## ###-####");
+        assert_eq!(masked["club"], "Juventus");
+        assert_eq!(masked["email"].as_str().unwrap().len(), 64);
+        assert_ne!(masked["email"], record["email"]);
+        assert!(masked["contact"]
+            .as_str()
+            .unwrap()
+            .ends_with("@example.com"));
+        assert!(masked["name"].as_str().unwrap().split(' ').count() >= 2);
+        assert_ne!(masked["name"], record["name"]);
+        assert_eq!(masked["kit"].as_str().unwrap().len(), 2);
+
+        // deterministic, so masked files still join
+        assert_eq!(masker.mask(record), masked);
+        let other = Masker::new(&columns, Some(&[7; 32]))?;
+        assert_ne!(
+            other.mask(json!({"email": "x@y.z"})),
+            masker.mask(json!({"email": "x@y.z"}))
+        );
+
+        assert!(Masker::new(&columns, None).is_err());
+        assert!(Masker::new(&columns[1..2], None).is_ok());
+        assert!(Masker::new(&columns, Some(b"short")).is_err());
+        assert!(parse_mask("email=scramble").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_names_do_not_collide() -> Result<()> {
+        let columns = vec![parse_mask("name=fake")?, parse_mask("email=fake")?];
+        let masker = Masker::new(&columns, Some(KEY))?;
+        let mut names = std::collections::HashSet::new();
+        let mut emails = std::collections::HashSet::new();
+        for i in 0..20_000 {
+            let record =
+                json!({"name": format!("Player {}", i), "email": format!("p{}@club.it", i)});
+            let masked = masker.mask(record);
+            assert!(names.insert(masked["name"].as_str().unwrap().to_string()));
+            assert!(emails.insert(masked["email"].as_str().unwrap().to_string()));
+        }
+        Ok(())
+    }
+}
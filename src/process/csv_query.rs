@@ -0,0 +1,226 @@
+use anyhow::{bail, Result};
+use rusqlite::{params_from_iter, types::ValueRef, Connection};
+use serde_json::{Map, Value};
+use std::{io::Write, path::Path};
+
+use super::{
+    csv_filter::as_text,
+    csv_show::{render_table, terminal_style},
+};
+use crate::{
+    build_reader, get_reader, infer_value, read_headers, write_output, write_records, CsvReadOpts,
+    CsvWriteOpts,
+};
+
+/// Load every input into an in-memory SQLite table named after its file and run `sql`.
+/// Without `--output` the result is printed as a table.
+pub fn process_csv_query(
+    sql: &str,
+    inputs: &[String],
+    opts: &CsvReadOpts,
+    write: &CsvWriteOpts,
+) -> Result<()> {
+    let mut conn = Connection::open_in_memory()?;
+    let mut tables: Vec<String> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let name = table_name(input);
+        if tables.contains(&name) {
+            bail!("two inputs map to the same table {:?}", name);
+        }
+        load_table(
+            &mut conn,
+            &name,
+            build_reader(get_reader(input)?, opts)?,
+            opts,
+        )?;
+        tables.push(name);
+    }
+
+    let (columns, records) = run_query(&conn, sql)?;
+    match &write.output {
+        Some(output) => write_output(output, |writer| {
+            write_records(writer, records.into_iter().map(Ok), write)
+        })?,
+        None => {
+            let rows: Vec<Vec<String>> = records
+                .iter()
+                .map(|record| columns.iter().map(|c| as_text(&record[c])).collect())
+                .collect();
+            let table = render_table(&columns, &rows, 40, terminal_style());
+            std::io::stdout().lock().write_all(table.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// `data/juventus-2019.csv` becomes `juventus_2019`, stdin becomes `stdin`.
+pub fn table_name(input: &str) -> String {
+    if input == "-" {
+        return "stdin".to_string();
+    }
+    let stem = Path::new(input)
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("t_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Create a table with one untyped column per header and insert every record.
+/// Cells are inferred, so numbers compare and aggregate as numbers.
+pub fn load_table<R: std::io::Read>(
+    conn: &mut Connection,
+    name: &str,
+    mut reader: csv::Reader<R>,
+    opts: &CsvReadOpts,
+) -> Result<()> {
+    let headers = read_headers(&mut reader, opts)?;
+    let columns: Vec<String> = headers.iter().map(quote_ident).collect();
+    conn.execute(
+        &format!(
+            "CREATE TABLE {} ({})",
+            quote_ident(name),
+            columns.join(", ")
+        ),
+        [],
+    )?;
+
+    let tx = conn.transaction()?;
+    {
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        let mut stmt = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_ident(name),
+            placeholders
+        ))?;
+        for result in reader.records() {
+            let record = result?;
+            // flexible records are padded or cut to the header
+            let cells =
+                (0..columns.len()).map(|i| to_sql(infer_value(record.get(i).unwrap_or_default())));
+            stmt.execute(params_from_iter(cells))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Run a single statement, returning its column names and one header-keyed record per row.
+/// A repeated column name, e.g. `id` from both sides of a join, gets a suffix: `id_2`.
+pub fn run_query(conn: &Connection, sql: &str) -> Result<(Vec<String>, Vec<Value>)> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns = unique_names(stmt.column_names());
+    let mut rows = stmt.query([])?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut map = Map::with_capacity(columns.len());
+        for (i, column) in columns.iter().enumerate() {
+            map.insert(column.clone(), from_sql(row.get_ref(i)?));
+        }
+        records.push(Value::Object(map));
+    }
+    Ok((columns, records))
+}
+
+fn unique_names(names: Vec<&str>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let mut candidate = name.to_string();
+        let mut n = 1;
+        while unique.contains(&candidate) {
+            n += 1;
+            candidate = format!("{}_{}", name, n);
+        }
+        unique.push(candidate);
+    }
+    unique
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn to_sql(value: Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as Sql;
+    match value {
+        Value::Null => Sql::Null,
+        Value::Bool(b) => Sql::Integer(b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Sql::Integer(i),
+            None => Sql::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => Sql::Text(s),
+        v => Sql::Text(v.to_string()),
+    }
+}
+
+fn from_sql(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(s) => String::from_utf8_lossy(s).into(),
+        ValueRef::Blob(b) => hex::encode(b).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_run_query() -> Result<()> {
+        let data = "\
+Name,Position,Kit Number
+Szczesny,Goalkeeper,1
+Chiellini,Centre-Back,3
+Buffon,Goalkeeper,77
+";
+        let opts = CsvReadOpts::default();
+        let mut conn = Connection::open_in_memory()?;
+        load_table(
+            &mut conn,
+            "juventus",
+            build_reader(data.as_bytes(), &opts)?,
+            &opts,
+        )?;
+        let sql = r#"SELECT Position, count(*) AS n, max("Kit Number") AS kit FROM juventus
+            GROUP BY Position ORDER BY n DESC"#;
+        let (columns, records) = run_query(&conn, sql)?;
+        assert_eq!(columns, vec!["Position", "n", "kit"]);
+        assert_eq!(
+            records,
+            vec![
+                json!({"Position": "Goalkeeper", "n": 2, "kit": 77}),
+                json!({"Position": "Centre-Back", "n": 1, "kit": 3}),
+            ]
+        );
+        assert!(run_query(&conn, "SELECT * FROM players").is_err());
+
+        let sql = "SELECT a.Name, b.Name, a.Position FROM juventus a JOIN juventus b
+            ON a.Position = b.Position AND a.Name < b.Name";
+        let (columns, records) = run_query(&conn, sql)?;
+        assert_eq!(columns, vec!["Name", "Name_2", "Position"]);
+        assert_eq!(
+            records,
+            vec![json!({"Name": "Buffon", "Name_2": "Szczesny", "Position": "Goalkeeper"})]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_name() {
+        assert_eq!(table_name("assets/juventus.csv"), "juventus");
+        assert_eq!(table_name("data/serie-a 2019.csv"), "serie_a_2019");
+        assert_eq!(table_name("2019.csv"), "t_2019");
+        assert_eq!(table_name("-"), "stdin");
+    }
+}
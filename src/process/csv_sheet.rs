@@ -0,0 +1,225 @@
+use anyhow::{anyhow, bail, Result};
+use calamine::{open_workbook_auto, Data, Reader};
+use csv::StringRecord;
+use std::{fmt, path::Path, str::FromStr};
+
+use crate::{CsvReadOpts, SheetOpts};
+
+const SPREADSHEET_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// A `--range` such as `A3:D40`. Without an end cell the range runs to the end of the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    /// (row, column), zero based
+    pub start: (u32, u32),
+    pub end: Option<(u32, u32)>,
+}
+
+pub fn is_spreadsheet(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SPREADSHEET_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+pub fn process_list_sheets(input: &str) -> Result<()> {
+    for name in open_workbook_auto(input)?.sheet_names() {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+/// Read a worksheet into a header and records, the same shape a CSV reader produces.
+/// Rows where every cell is empty are skipped.
+pub fn read_sheet(
+    input: &str,
+    sheet: &SheetOpts,
+    opts: &CsvReadOpts,
+) -> Result<(StringRecord, Vec<StringRecord>)> {
+    let mut workbook = open_workbook_auto(input)?;
+    let name = match &sheet.sheet {
+        Some(name) => name.clone(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow!("{} has no worksheets", input))?,
+    };
+    let mut range = workbook
+        .worksheet_range(&name)
+        .map_err(|e| anyhow!("cannot read sheet {:?}: {}", name, e))?;
+    if let (Some(cells), Some(used_start), Some(used_end)) =
+        (sheet.range, range.start(), range.end())
+    {
+        // only the part of the range that overlaps the used area of the sheet
+        let end = cells.end.unwrap_or(used_end);
+        let start = (
+            cells.start.0.max(used_start.0),
+            cells.start.1.max(used_start.1),
+        );
+        let end = (end.0.min(used_end.0), end.1.min(used_end.1));
+        if start.0 > end.0 || start.1 > end.1 {
+            bail!(
+                "range {} is outside sheet {:?}, which spans {}:{}",
+                cells,
+                name,
+                cell_name(used_start),
+                cell_name(used_end)
+            );
+        }
+        range = range.range(start, end);
+    }
+
+    let mut rows = range
+        .rows()
+        .filter(|row| row.iter().any(|cell| *cell != Data::Empty))
+        .map(|row| -> StringRecord {
+            let mut record: StringRecord = row.iter().map(cell_to_string).collect();
+            if opts.trim {
+                record.trim();
+            }
+            record
+        });
+    let headers = match (opts.header, rows.next()) {
+        (true, Some(headers)) => headers,
+        (true, None) => StringRecord::new(),
+        (false, first) => {
+            let records: Vec<StringRecord> = first.into_iter().chain(rows).collect();
+            let width = records.iter().map(|r| r.len()).max().unwrap_or_default();
+            let headers = (0..width).map(|i| format!("col_{}", i)).collect();
+            return Ok((headers, records));
+        }
+    };
+    Ok((headers, rows.collect()))
+}
+
+/// Whole floats are written without a fraction; dates use ISO 8601.
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", *f as i64),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => dt.format("%Y-%m-%d").to_string(),
+            Some(dt) => dt.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            None => dt.to_string(),
+        },
+        cell => cell.to_string(),
+    }
+}
+
+// (2, 1) -> "B3"
+fn cell_name((row, col): (u32, u32)) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        letters.push(b'A' + ((n - 1) % 26) as u8);
+        n = (n - 1) / 26;
+    }
+    letters.reverse();
+    format!("{}{}", String::from_utf8_lossy(&letters), row + 1)
+}
+
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}:{}", cell_name(self.start), cell_name(end)),
+            None => write!(f, "{}", cell_name(self.start)),
+        }
+    }
+}
+
+impl FromStr for CellRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = match s.split_once(':') {
+            Some((start, end)) => (parse_cell(start)?, Some(parse_cell(end)?)),
+            None => (parse_cell(s)?, None),
+        };
+        if end.is_some_and(|(row, col)| row < start.0 || col < start.1) {
+            bail!("range {:?} ends before it starts", s);
+        }
+        Ok(CellRange { start, end })
+    }
+}
+
+// "B3" -> (2, 1)
+fn parse_cell(cell: &str) -> Result<(u32, u32)> {
+    let cell = cell.trim().to_uppercase();
+    let split = cell
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(cell.len());
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || letters.len() > 3 {
+        bail!("invalid cell {:?}, expect a reference like B3", cell);
+    }
+    let col = letters
+        .bytes()
+        .fold(0u32, |col, b| col * 26 + (b - b'A' + 1) as u32);
+    let row: u32 = digits
+        .parse()
+        .ok()
+        .filter(|&row| row > 0)
+        .ok_or_else(|| anyhow!("invalid cell {:?}, expect a reference like B3", cell))?;
+    Ok((row - 1, col - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sheet() -> Result<()> {
+        let opts = CsvReadOpts::default();
+        let sheet = SheetOpts {
+            range: Some("A3".parse()?),
+            ..Default::default()
+        };
+        let (headers, records) = read_sheet("fixtures/players.xlsx", &sheet, &opts)?;
+        assert_eq!(headers, vec!["Name", "Position", "Kit Number", "Captain"]);
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[2],
+            vec!["Mattia Perin", "Goalkeeper", "37", "false"]
+        );
+
+        let sheet = SheetOpts {
+            sheet: Some("Notes".into()),
+            ..Default::default()
+        };
+        let (headers, records) = read_sheet("fixtures/players.xlsx", &sheet, &opts)?;
+        assert_eq!(headers, vec!["Note"]);
+        assert_eq!(
+            records,
+            vec![StringRecord::from(vec!["exported from the club portal"])]
+        );
+
+        for outside in ["A100", "Z1", "E1:F2"] {
+            let sheet = SheetOpts {
+                range: Some(outside.parse()?),
+                ..Default::default()
+            };
+            assert!(read_sheet("fixtures/players.xlsx", &sheet, &opts).is_err());
+        }
+        let sheet = SheetOpts {
+            range: Some("A3:Z100".parse()?),
+            ..Default::default()
+        };
+        let (headers, records) = read_sheet("fixtures/players.xlsx", &sheet, &opts)?;
+        assert_eq!(headers.len(), 4);
+        assert_eq!(records.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_cell_range() {
+        let range: CellRange = "B3:AA10".parse().unwrap();
+        assert_eq!(range.start, (2, 1));
+        assert_eq!(range.end, Some((9, 26)));
+        assert_eq!("c5".parse::<CellRange>().unwrap().end, None);
+        assert!("D4:A1".parse::<CellRange>().is_err());
+        assert!("3B".parse::<CellRange>().is_err());
+        assert!("A0".parse::<CellRange>().is_err());
+        assert_eq!(range.to_string(), "B3:AA10");
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{build_reader, get_reader, read_headers, CsvReadOpts};
+
+pub struct TableStyle {
+    horizontal: char,
+    vertical: char,
+    // top, middle and bottom rows: left, cross, right
+    corners: [[char; 3]; 3],
+    ellipsis: &'static str,
+}
+
+pub const UNICODE_STYLE: TableStyle = TableStyle {
+    horizontal: '─',
+    vertical: '│',
+    corners: [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']],
+    ellipsis: "…",
+};
+
+pub const ASCII_STYLE: TableStyle = TableStyle {
+    horizontal: '-',
+    vertical: '|',
+    corners: [['+', '+', '+'], ['+', '+', '+'], ['+', '+', '+']],
+    ellipsis: "...",
+};
+
+pub fn process_csv_show(
+    input: &str,
+    opts: &CsvReadOpts,
+    select: &[String],
+    head: Option<usize>,
+    tail: Option<usize>,
+    max_width: usize,
+) -> Result<()> {
+    let mut reader = build_reader(get_reader(input)?, opts)?;
+    let headers = read_headers(&mut reader, opts)?;
+    let indices = select_columns(&headers, select)?;
+    let pick = |record: &StringRecord| -> Vec<String> {
+        indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default().to_string())
+            .collect()
+    };
+
+    let mut rows = VecDeque::new();
+    for (n, result) in reader.records().enumerate() {
+        if head.is_some_and(|head| n >= head) {
+            break;
+        }
+        rows.push_back(pick(&result?));
+        if tail.is_some_and(|tail| rows.len() > tail) {
+            rows.pop_front();
+        }
+    }
+
+    let table = render_table(
+        &pick(&headers),
+        rows.make_contiguous(),
+        max_width,
+        terminal_style(),
+    );
+    std::io::stdout().lock().write_all(table.as_bytes())?;
+    Ok(())
+}
+
+// box drawing characters only make sense on a terminal; keep pipes and files plain
+pub fn terminal_style() -> &'static TableStyle {
+    if std::io::stdout().is_terminal() {
+        &UNICODE_STYLE
+    } else {
+        &ASCII_STYLE
+    }
+}
+
+fn select_columns(headers: &StringRecord, select: &[String]) -> Result<Vec<usize>> {
+    if select.is_empty() {
+        return Ok((0..headers.len()).collect());
+    }
+    select
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| anyhow!("column {:?} not found", column))
+        })
+        .collect()
+}
+
+pub fn render_table(
+    headers: &[String],
+    rows: &[Vec<String>],
+    max_width: usize,
+    style: &TableStyle,
+) -> String {
+    let fit = |cell: &str| truncate(&sanitize(cell), max_width, style.ellipsis);
+    let headers: Vec<String> = headers.iter().map(|h| fit(h)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| fit(cell)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let mut out = String::new();
+    push_border(&mut out, &widths, style, 0);
+    push_row(&mut out, &headers, &widths, style);
+    push_border(&mut out, &widths, style, 1);
+    for row in &rows {
+        push_row(&mut out, row, &widths, style);
+    }
+    push_border(&mut out, &widths, style, 2);
+    out
+}
+
+fn push_border(out: &mut String, widths: &[usize], style: &TableStyle, line: usize) {
+    let [left, cross, right] = style.corners[line];
+    out.push(left);
+    for (i, width) in widths.iter().enumerate() {
+        if i > 0 {
+            out.push(cross);
+        }
+        out.extend(std::iter::repeat_n(style.horizontal, width + 2));
+    }
+    out.push(right);
+    out.push('\n');
+}
+
+fn push_row(out: &mut String, cells: &[String], widths: &[usize], style: &TableStyle) {
+    out.push(style.vertical);
+    for (cell, width) in cells.iter().zip(widths) {
+        out.push(' ');
+        out.push_str(cell);
+        out.extend(std::iter::repeat_n(' ', width - cell.width() + 1));
+        out.push(style.vertical);
+    }
+    out.push('\n');
+}
+
+// embedded line breaks and tabs would break the row layout
+fn sanitize(cell: &str) -> String {
+    cell.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Cut a cell to at most `max_width` terminal columns, counting wide (e.g. CJK) characters as two.
+fn truncate(cell: &str, max_width: usize, ellipsis: &str) -> String {
+    if cell.width() <= max_width {
+        return cell.to_string();
+    }
+    let budget = max_width.saturating_sub(ellipsis.width());
+    let mut width = 0;
+    let mut out = String::new();
+    for c in cell.chars() {
+        width += c.width().unwrap_or(0);
+        if width > budget {
+            break;
+        }
+        out.push(c);
+    }
+    out.push_str(ellipsis);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Goalkeeper", 20, "…"), "Goalkeeper");
+        assert_eq!(truncate("Goalkeeper", 5, "…"), "Goal…");
+        assert_eq!(truncate("尤文图斯", 5, "…"), "尤文…");
+        assert_eq!(truncate("Goalkeeper", 6, "..."), "Goa...");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["Perin".to_string(), "37".to_string()],
+            vec!["布冯".to_string(), "1".to_string()],
+        ];
+        let table = render_table(&headers, &rows, 30, &ASCII_STYLE);
+        let expected = "\
++-------+-----+
+| Name  | Kit |
++-------+-----+
+| Perin | 37  |
+| 布冯  | 1   |
++-------+-----+
+";
+        assert_eq!(table, expected);
+    }
+}
@@ -0,0 +1,347 @@
+use anyhow::{anyhow, bail, Result};
+use csv::{StringRecord, WriterBuilder};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{build_reader, get_reader, read_headers, CsvReadOpts};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitBy {
+    Rows(usize),
+    Size(u64),
+    Column(String),
+}
+
+pub fn process_csv_split(
+    input: &str,
+    split: &SplitBy,
+    output_dir: &Path,
+    opts: &CsvReadOpts,
+) -> Result<()> {
+    let mut reader = build_reader(get_reader(input)?, opts)?;
+    let headers = read_headers(&mut reader, opts)?;
+    let stem = Path::new(input)
+        .file_stem()
+        .filter(|_| input != "-")
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "stdin".to_string());
+    for path in split_csv(reader, &headers, split, opts, output_dir, &stem)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+// parts of a column split that stay open at once; the least recently used one is closed and
+// reopened for append when its value comes back
+const MAX_OPEN_PARTS: usize = 64;
+
+struct Part {
+    // `None` while closed to free its file handle
+    writer: Option<BufWriter<File>>,
+    path: PathBuf,
+    records: usize,
+    bytes: u64,
+    // when the part was last written to, for closing the least recently used
+    used: u64,
+}
+
+/// Stream records into parts named `<stem>_0001.csv`, or `<stem>_<value>.csv` when splitting by
+/// column, each starting with the header. Records keep the input delimiter and quote.
+/// Returns the paths written, in the order they were created. Fails rather than overwrite a
+/// part that already exists.
+pub fn split_csv<R: Read>(
+    mut reader: csv::Reader<R>,
+    headers: &StringRecord,
+    split: &SplitBy,
+    opts: &CsvReadOpts,
+    dir: &Path,
+    stem: &str,
+) -> Result<Vec<PathBuf>> {
+    let column = match split {
+        SplitBy::Rows(0) | SplitBy::Size(0) => bail!("--rows and --size must be greater than zero"),
+        SplitBy::Column(column) => Some(
+            headers
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| anyhow!("column {:?} not found", column))?,
+        ),
+        _ => None,
+    };
+    let extension = if opts.delimiter == b'\t' {
+        "tsv"
+    } else {
+        "csv"
+    };
+    let mut builder = WriterBuilder::new();
+    builder
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .flexible(opts.flexible)
+        .buffer_capacity(1024);
+    // encoded one record at a time so a part knows its size before writing
+    let encode = |record: &StringRecord| -> Result<Vec<u8>> {
+        let mut encoder = builder.from_writer(Vec::new());
+        encoder.write_record(record)?;
+        encoder.into_inner().map_err(|e| anyhow!("{}", e.error()))
+    };
+    let header = encode(headers)?;
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut open = |name: String| -> Result<Part> {
+        let path = dir.join(format!("{}_{}.{}", stem, name, extension));
+        if paths.contains(&path) {
+            bail!("two values of the split column map to {}", path.display());
+        }
+        // never overwrite the parts of an earlier run
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => anyhow!("{} already exists", path.display()),
+                _ => e.into(),
+            })?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&header)?;
+        paths.push(path.clone());
+        Ok(Part {
+            writer: Some(writer),
+            path,
+            records: 0,
+            bytes: header.len() as u64,
+            used: 0,
+        })
+    };
+
+    // splitting by column keeps a part per value, up to MAX_OPEN_PARTS of them open;
+    // the other modes write a single part at a time
+    let mut parts: HashMap<String, Part> = HashMap::new();
+    let mut lru: BTreeMap<u64, String> = BTreeMap::new();
+    let mut current: Option<Part> = None;
+    let mut record = StringRecord::new();
+    let mut count = 0;
+    let mut line = 0;
+    while reader.read_record(&mut record)? {
+        let bytes = encode(&record)?;
+        line += 1;
+        let part = match (split, column) {
+            (_, Some(i)) => {
+                let value = record.get(i).unwrap_or_default().to_string();
+                if parts.get(&value).is_none_or(|part| part.writer.is_none()) {
+                    if lru.len() >= MAX_OPEN_PARTS {
+                        if let Some((_, evicted)) = lru.pop_first() {
+                            let evicted = parts.get_mut(&evicted).expect("open parts are known");
+                            if let Some(mut writer) = evicted.writer.take() {
+                                writer.flush()?;
+                            }
+                        }
+                    }
+                    match parts.entry(value.clone()) {
+                        Entry::Occupied(mut entry) => {
+                            let part = entry.get_mut();
+                            let file = OpenOptions::new().append(true).open(&part.path)?;
+                            part.writer = Some(BufWriter::new(file));
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(open(part_name(&value))?);
+                        }
+                    }
+                }
+                let part = parts.get_mut(&value).expect("opened above");
+                lru.remove(&part.used);
+                part.used = line;
+                lru.insert(line, value);
+                part
+            }
+            (split, None) => {
+                let full = current.as_ref().is_some_and(|part| match split {
+                    SplitBy::Rows(rows) => part.records >= *rows,
+                    SplitBy::Size(size) => {
+                        part.records > 0 && part.bytes + bytes.len() as u64 > *size
+                    }
+                    SplitBy::Column(_) => false,
+                });
+                if full || current.is_none() {
+                    if let Some(mut writer) = current.take().and_then(|part| part.writer) {
+                        writer.flush()?;
+                    }
+                    count += 1;
+                    current = Some(open(format!("{:04}", count))?);
+                }
+                current.as_mut().expect("opened above")
+            }
+        };
+        let writer = part.writer.as_mut().expect("parts are open while written");
+        writer.write_all(&bytes)?;
+        part.records += 1;
+        part.bytes += bytes.len() as u64;
+    }
+    for part in current.iter_mut().chain(parts.values_mut()) {
+        if let Some(writer) = &mut part.writer {
+            writer.flush()?;
+        }
+    }
+    Ok(paths)
+}
+
+// keep column values safe to use in a file name
+fn part_name(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Sizes like "50MB", "512KiB" or "1.5G". Units are powers of 1000, or 1024 with an "i".
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid size {:?}, expect something like 50MB", value))?;
+    let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1_000,
+        "M" | "MB" => 1_000_000,
+        "G" | "GB" => 1_000_000_000,
+        "KI" | "KIB" => 1 << 10,
+        "MI" | "MIB" => 1 << 20,
+        "GI" | "GIB" => 1 << 30,
+        unit => bail!("unknow size unit {:?}", unit),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_reader;
+
+    const DATA: &str = "\
+Name,Region,Note
+Buffon,Piedmont,\"keeper, captain\"
+Chiellini,Piedmont,
+Perin,Liguria,
+Insigne,Campania,
+Immobile,Campania,
+";
+
+    fn split(split: &SplitBy, name: &str) -> Result<Vec<(String, String)>> {
+        let dir = std::env::temp_dir().join(format!("rcli-split-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let opts = CsvReadOpts::default();
+        let mut reader = build_reader(DATA.as_bytes(), &opts)?;
+        let headers = read_headers(&mut reader, &opts)?;
+        let paths = split_csv(reader, &headers, split, &opts, &dir, "players")?;
+        let parts = paths
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                Ok((name, std::fs::read_to_string(path)?))
+            })
+            .collect();
+        std::fs::remove_dir_all(&dir)?;
+        parts
+    }
+
+    #[test]
+    fn test_split_csv() -> Result<()> {
+        let parts = split(&SplitBy::Rows(2), "rows")?;
+        let names: Vec<_> = parts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["players_0001.csv", "players_0002.csv", "players_0003.csv"]
+        );
+        assert_eq!(
+            parts[0].1,
+            "Name,Region,Note\nBuffon,Piedmont,\"keeper, captain\"\nChiellini,Piedmont,\n"
+        );
+        assert_eq!(parts[2].1, "Name,Region,Note\nImmobile,Campania,\n");
+
+        // every part holds the header plus at least one record, even past the limit
+        let parts = split(&SplitBy::Size(40), "size")?;
+        assert_eq!(parts.len(), 5);
+        assert!(parts.iter().skip(1).all(|(_, part)| part.len() <= 40));
+
+        let parts = split(&SplitBy::Column("Region".into()), "column")?;
+        assert_eq!(parts[2].0, "players_Campania.csv");
+        assert_eq!(
+            parts[2].1,
+            "Name,Region,Note\nInsigne,Campania,\nImmobile,Campania,\n"
+        );
+        assert!(split(&SplitBy::Column("Club".into()), "missing").is_err());
+
+        let dir = std::env::temp_dir().join(format!("rcli-split-exists-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("players_0002.csv"), "keep")?;
+        let opts = CsvReadOpts::default();
+        let mut reader = build_reader(DATA.as_bytes(), &opts)?;
+        let headers = read_headers(&mut reader, &opts)?;
+        let ret = split_csv(reader, &headers, &SplitBy::Rows(2), &opts, &dir, "players");
+        let kept = std::fs::read_to_string(dir.join("players_0002.csv"))?;
+        std::fs::remove_dir_all(&dir)?;
+        assert!(ret.is_err());
+        assert_eq!(kept, "keep");
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_csv_many_values() -> Result<()> {
+        let values = MAX_OPEN_PARTS * 2;
+        let mut data = "Name,Team\n".to_string();
+        for i in 0..values * 3 {
+            data.push_str(&format!("player{},team{}\n", i, i % values));
+        }
+        let dir = std::env::temp_dir().join(format!("rcli-split-many-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let opts = CsvReadOpts::default();
+        let mut reader = build_reader(data.as_bytes(), &opts)?;
+        let headers = read_headers(&mut reader, &opts)?;
+        let paths = split_csv(
+            reader,
+            &headers,
+            &SplitBy::Column("Team".into()),
+            &opts,
+            &dir,
+            "players",
+        )?;
+        assert_eq!(paths.len(), values);
+        let first = std::fs::read_to_string(&paths[0])?;
+        let last = std::fs::read_to_string(&paths[values - 1])?;
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(
+            first,
+            format!(
+                "Name,Team\nplayer0,team0\nplayer{},team0\nplayer{},team0\n",
+                values,
+                values * 2
+            )
+        );
+        assert_eq!(last.lines().count(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_size() -> Result<()> {
+        assert_eq!(parse_size("50MB")?, 50_000_000);
+        assert_eq!(parse_size("512KiB")?, 512 * 1024);
+        assert_eq!(parse_size("1.5g")?, 1_500_000_000);
+        assert_eq!(parse_size("300")?, 300);
+        assert!(parse_size("50XB").is_err());
+        assert!(parse_size("MB").is_err());
+        Ok(())
+    }
+}
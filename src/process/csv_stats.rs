@@ -0,0 +1,436 @@
+use anyhow::Result;
+use csv::StringRecord;
+use serde::Serialize;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+};
+
+use super::csv_show::{render_table, terminal_style};
+use crate::{
+    build_reader, get_reader, infer_type, read_headers, ColumnType, CsvReadOpts, StatsFormat,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ColumnProfile {
+    pub column: String,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    pub count: u64,
+    pub nulls: u64,
+    pub distinct: u64,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TopValue {
+    pub value: String,
+    pub count: u64,
+}
+
+pub fn process_csv_stats(
+    input: &str,
+    opts: &CsvReadOpts,
+    approx: bool,
+    top: usize,
+    format: StatsFormat,
+) -> Result<()> {
+    let mut reader = build_reader(get_reader(input)?, opts)?;
+    let headers = read_headers(&mut reader, opts)?;
+    let profiles = profile_records(reader, &headers, approx, top)?;
+
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        StatsFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &profiles)?;
+            writeln!(stdout)?;
+        }
+        StatsFormat::Table => {
+            let (headers, rows) = profile_rows(&profiles);
+            let table = render_table(&headers, &rows, 40, terminal_style());
+            stdout.write_all(table.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Profile every column in one pass. Cells missing from short rows count as nulls.
+pub fn profile_records<R: Read>(
+    mut reader: csv::Reader<R>,
+    headers: &StringRecord,
+    approx: bool,
+    top: usize,
+) -> Result<Vec<ColumnProfile>> {
+    let mut stats: Vec<ColumnStats> = headers
+        .iter()
+        .map(|_| ColumnStats::new(approx, top))
+        .collect();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        for (i, stat) in stats.iter_mut().enumerate() {
+            stat.update(record.get(i).unwrap_or_default());
+        }
+    }
+    Ok(headers
+        .iter()
+        .zip(stats)
+        .map(|(column, stat)| stat.finish(column))
+        .collect())
+}
+
+fn profile_rows(profiles: &[ColumnProfile]) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers = [
+        "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "stddev", "top",
+    ];
+    let float = |f: Option<f64>| f.map(|f| format!("{:.2}", f)).unwrap_or_default();
+    let rows = profiles
+        .iter()
+        .map(|p| {
+            let top = p
+                .top
+                .iter()
+                .map(|top| format!("{} ({})", top.value, top.count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            vec![
+                p.column.clone(),
+                p.ty.clone().unwrap_or_default(),
+                p.count.to_string(),
+                p.nulls.to_string(),
+                p.distinct.to_string(),
+                p.min.clone().unwrap_or_default(),
+                p.max.clone().unwrap_or_default(),
+                float(p.mean),
+                float(p.stddev),
+                top,
+            ]
+        })
+        .collect();
+    (headers.iter().map(|h| h.to_string()).collect(), rows)
+}
+
+enum Frequencies {
+    Exact(HashMap<String, u64>),
+    Approx(HyperLogLog, SpaceSaving),
+}
+
+struct ColumnStats {
+    ty: Option<ColumnType>,
+    count: u64,
+    nulls: u64,
+    frequencies: Frequencies,
+    top: usize,
+    min: Option<String>,
+    max: Option<String>,
+    min_num: f64,
+    max_num: f64,
+    // Welford's online mean and variance
+    mean: f64,
+    m2: f64,
+    numbers: u64,
+}
+
+impl ColumnStats {
+    fn new(approx: bool, top: usize) -> Self {
+        let frequencies = if approx {
+            Frequencies::Approx(HyperLogLog::new(), SpaceSaving::new((top * 50).max(100)))
+        } else {
+            Frequencies::Exact(HashMap::new())
+        };
+        Self {
+            ty: None,
+            count: 0,
+            nulls: 0,
+            frequencies,
+            top,
+            min: None,
+            max: None,
+            min_num: f64::INFINITY,
+            max_num: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            numbers: 0,
+        }
+    }
+
+    fn update(&mut self, cell: &str) {
+        self.count += 1;
+        let Some(ty) = infer_type(cell) else {
+            self.nulls += 1;
+            return;
+        };
+        self.ty = Some(merge_types(self.ty, ty));
+
+        match &mut self.frequencies {
+            Frequencies::Exact(counts) => match counts.get_mut(cell) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(cell.to_string(), 1);
+                }
+            },
+            Frequencies::Approx(hll, top) => {
+                hll.insert(cell);
+                top.insert(cell);
+            }
+        }
+
+        if self.min.as_deref().is_none_or(|min| cell < min) {
+            self.min = Some(cell.to_string());
+        }
+        if self.max.as_deref().is_none_or(|max| cell > max) {
+            self.max = Some(cell.to_string());
+        }
+        if let Ok(n) = cell.parse::<f64>() {
+            if matches!(ty, ColumnType::Int | ColumnType::Float) {
+                self.numbers += 1;
+                self.min_num = self.min_num.min(n);
+                self.max_num = self.max_num.max(n);
+                let delta = n - self.mean;
+                self.mean += delta / self.numbers as f64;
+                self.m2 += delta * (n - self.mean);
+            }
+        }
+    }
+
+    fn finish(self, column: &str) -> ColumnProfile {
+        let numeric = matches!(self.ty, Some(ColumnType::Int | ColumnType::Float));
+        let (distinct, mut top): (u64, Vec<_>) = match self.frequencies {
+            Frequencies::Exact(counts) => (counts.len() as u64, counts.into_iter().collect()),
+            Frequencies::Approx(hll, top) => (hll.count(), top.into_counts()),
+        };
+        sort_top(&mut top);
+        top.truncate(self.top);
+
+        let (min, max, mean, stddev) = if numeric {
+            let stddev = (self.numbers > 1).then(|| (self.m2 / (self.numbers - 1) as f64).sqrt());
+            (
+                Some(format_number(self.min_num)),
+                Some(format_number(self.max_num)),
+                Some(self.mean),
+                stddev,
+            )
+        } else {
+            (self.min, self.max, None, None)
+        };
+        ColumnProfile {
+            column: column.to_string(),
+            ty: self.ty.map(|ty| ty.to_string()),
+            count: self.count,
+            nulls: self.nulls,
+            distinct,
+            min,
+            max,
+            mean,
+            stddev,
+            top: top
+                .into_iter()
+                .map(|(value, count)| TopValue { value, count })
+                .collect(),
+        }
+    }
+}
+
+/// The narrowest type every value of a column fits in.
+pub fn merge_types(current: Option<ColumnType>, ty: ColumnType) -> ColumnType {
+    match (current, ty) {
+        (None, ty) => ty,
+        (Some(a), b) if a == b => a,
+        (Some(ColumnType::Int | ColumnType::Float), ColumnType::Int | ColumnType::Float) => {
+            ColumnType::Float
+        }
+        _ => ColumnType::String,
+    }
+}
+
+fn sort_top(top: &mut [(String, u64)]) {
+    top.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+const HLL_PRECISION: u32 = 14;
+
+/// HyperLogLog distinct counter: 16K one-byte registers, ~0.8% standard error.
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let hash = hash(value);
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    fn count(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // linear counting is more accurate for small cardinalities
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// Space-Saving heavy hitters: tracks at most `capacity` values, counts may overestimate.
+struct SpaceSaving {
+    capacity: usize,
+    // value -> (count, seq)
+    counts: HashMap<String, (u64, u64)>,
+    // the same counters ordered by (count, seq), so the least frequent one is found in O(log n)
+    order: BTreeMap<(u64, u64), String>,
+    seq: u64,
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::with_capacity(capacity),
+            order: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        self.seq += 1;
+        if let Some(counter) = self.counts.get_mut(value) {
+            let value = self.order.remove(counter).expect("counters are ordered");
+            *counter = (counter.0 + 1, self.seq);
+            self.order.insert(*counter, value);
+            return;
+        }
+        let mut count = 1;
+        if self.counts.len() >= self.capacity {
+            // replace the least frequent value, inheriting its count
+            if let Some(((min_count, _), min_value)) = self.order.pop_first() {
+                self.counts.remove(&min_value);
+                count = min_count + 1;
+            }
+        }
+        self.counts.insert(value.to_string(), (count, self.seq));
+        self.order.insert((count, self.seq), value.to_string());
+    }
+
+    fn into_counts(self) -> Vec<(String, u64)> {
+        self.counts
+            .into_iter()
+            .map(|(value, (count, _))| (value, count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_stats() {
+        let mut stats = ColumnStats::new(false, 2);
+        for cell in ["1", "37", "", "1", "77"] {
+            stats.update(cell);
+        }
+        let profile = stats.finish("Kit Number");
+        assert_eq!(profile.ty.as_deref(), Some("int"));
+        assert_eq!((profile.count, profile.nulls, profile.distinct), (5, 1, 3));
+        assert_eq!(profile.min.as_deref(), Some("1"));
+        assert_eq!(profile.max.as_deref(), Some("77"));
+        assert_eq!(profile.mean, Some(29.0));
+        assert_eq!(
+            profile.top,
+            vec![
+                TopValue {
+                    value: "1".into(),
+                    count: 2
+                },
+                TopValue {
+                    value: "37".into(),
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_records_short_rows() -> Result<()> {
+        let opts = CsvReadOpts {
+            flexible: true,
+            ..Default::default()
+        };
+        let data = "Name,Club,Kit\nBuffon,Juventus,1\nPerin\nChiellini,LAFC\n";
+        let mut reader = build_reader(data.as_bytes(), &opts)?;
+        let headers = read_headers(&mut reader, &opts)?;
+        let profiles = profile_records(reader, &headers, false, 3)?;
+        let counts: Vec<_> = profiles.iter().map(|p| (p.count, p.nulls)).collect();
+        assert_eq!(counts, vec![(3, 0), (3, 1), (3, 2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_space_saving() {
+        let mut top = SpaceSaving::new(3);
+        for value in ["a", "a", "a", "b", "b", "c", "d", "a", "e"] {
+            top.insert(value);
+        }
+        let mut counts = top.into_counts();
+        sort_top(&mut counts);
+        // "d" replaces "c", then "e" replaces "b", the older of the two values counted twice
+        let expected = [("a", 4), ("e", 3), ("d", 2)];
+        assert_eq!(
+            counts,
+            expected.map(|(value, count)| (value.to_string(), count))
+        );
+    }
+
+    #[test]
+    fn test_merge_types() {
+        assert_eq!(
+            merge_types(Some(ColumnType::Int), ColumnType::Float),
+            ColumnType::Float
+        );
+        assert_eq!(
+            merge_types(Some(ColumnType::Int), ColumnType::Date),
+            ColumnType::String
+        );
+        assert_eq!(merge_types(None, ColumnType::Bool), ColumnType::Bool);
+    }
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..100_000 {
+            hll.insert(&format!("player-{}", i % 50_000));
+        }
+        let count = hll.count() as f64;
+        assert!((count - 50_000.0).abs() / 50_000.0 < 0.03, "{}", count);
+    }
+}
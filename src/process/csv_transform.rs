@@ -0,0 +1,362 @@
+use anyhow::{anyhow, bail, Result};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, str::FromStr};
+
+use super::csv_filter::{as_number, as_text};
+use crate::{load_masker, CsvTransformOpts, IndexStyle};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub desc: bool,
+}
+
+/// Apply `--where`, `--sort-by`, `--mask` and the column selection to a stream of records, in that order.
+/// Sorting has to buffer every record; everything else stays streaming.
+pub fn transform_records<'a, I>(
+    records: I,
+    headers: &StringRecord,
+    opts: &'a CsvTransformOpts,
+) -> Result<Box<dyn Iterator<Item = Result<Value>> + 'a>>
+where
+    I: Iterator<Item = Result<Value>> + 'a,
+{
+    let columns = opts
+        .select
+        .iter()
+        .chain(&opts.exclude)
+        .chain(opts.rename.iter().map(|(old, _)| old))
+        .chain(opts.mask.iter().map(|(column, _)| column))
+        .chain(opts.sort_by.iter().map(|key| &key.column))
+        .map(String::as_str)
+        .chain(opts.filter.iter().flat_map(|filter| filter.columns()));
+    for column in columns {
+        if !headers.iter().any(|h| h == column) {
+            bail!("column {:?} not found", column);
+        }
+    }
+    check_renames(headers, opts)?;
+
+    let masker = load_masker(&opts.mask, opts.mask_key.as_deref())?;
+    let filtered = records.filter(move |record| match (record, &opts.filter) {
+        (Ok(Value::Object(map)), Some(filter)) => filter.matches(map),
+        _ => true,
+    });
+    let sorted: Box<dyn Iterator<Item = Result<Value>>> = if opts.sort_by.is_empty() {
+        Box::new(filtered)
+    } else {
+        let mut records = filtered.collect::<Result<Vec<_>>>()?;
+        records.sort_by(|a, b| compare_records(a, b, &opts.sort_by));
+        Box::new(records.into_iter().map(Ok))
+    };
+    Ok(Box::new(sorted.map(move |record| {
+        let record = project(masker.mask(record?), opts);
+        match &opts.unflatten {
+            Some(separator) => unflatten(record, separator, opts.index_style),
+            None => Ok(record),
+        }
+    })))
+}
+
+fn compare_records(a: &Value, b: &Value, keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let ordering = compare_values(&a[&key.column], &b[&key.column]);
+        let ordering = if key.desc {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Numbers compare numerically, everything else as text. Nulls sort last.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => as_text(a).cmp(&as_text(b)),
+        },
+    }
+}
+
+// a rename must not land on a column that is still in the output, or on another rename
+fn check_renames(headers: &StringRecord, opts: &CsvTransformOpts) -> Result<()> {
+    let kept: Vec<&str> = if opts.select.is_empty() {
+        headers
+            .iter()
+            .filter(|h| !opts.exclude.iter().any(|e| e == h))
+            .collect()
+    } else {
+        opts.select.iter().map(String::as_str).collect()
+    };
+    for (i, (old, new)) in opts.rename.iter().enumerate() {
+        let renamed_away = |column: &str| opts.rename.iter().any(|(old, _)| old == column);
+        if kept.iter().any(|&k| k == new && !renamed_away(k)) {
+            bail!(
+                "cannot rename {:?} to {:?}, the column already exists",
+                old,
+                new
+            );
+        }
+        if opts.rename[..i].iter().any(|(_, other)| other == new) {
+            bail!("two columns are renamed to {:?}", new);
+        }
+    }
+    Ok(())
+}
+
+fn project(record: Value, opts: &CsvTransformOpts) -> Value {
+    let Value::Object(mut map) = record else {
+        return record;
+    };
+    if !opts.select.is_empty() {
+        map = opts
+            .select
+            .iter()
+            .map(|column| (column.clone(), map.remove(column).unwrap_or_default()))
+            .collect();
+    }
+    for column in &opts.exclude {
+        map.shift_remove(column);
+    }
+    if !opts.rename.is_empty() {
+        map = map
+            .into_iter()
+            .map(
+                |(k, v)| match opts.rename.iter().find(|(old, _)| *old == k) {
+                    Some((_, new)) => (new.clone(), v),
+                    None => (k, v),
+                },
+            )
+            .collect::<Map<_, _>>();
+    }
+    Value::Object(map)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// guards against a typo like `tags[100000000]` allocating a huge array
+const MAX_INDEX: usize = 65535;
+
+/// Turn a flat record with keys like `address.city` or `tags[0]` into nested objects and arrays.
+pub fn unflatten(record: Value, separator: &str, style: IndexStyle) -> Result<Value> {
+    let Value::Object(map) = record else {
+        return Ok(record);
+    };
+    let mut nested = Value::Object(Map::new());
+    for (key, value) in map {
+        let path = parse_path(&key, separator, style)?;
+        insert_path(&mut nested, &path, value)
+            .map_err(|_| anyhow!("column {:?} conflicts with another column", key))?;
+    }
+    Ok(nested)
+}
+
+fn parse_path(key: &str, separator: &str, style: IndexStyle) -> Result<Vec<PathSegment>> {
+    let parts: Vec<&str> = if separator.is_empty() {
+        vec![key]
+    } else {
+        key.split(separator).collect()
+    };
+    let mut path = Vec::with_capacity(parts.len());
+    for part in parts {
+        match style {
+            IndexStyle::Separator => match part.parse::<usize>() {
+                Ok(i) if !path.is_empty() => path.push(PathSegment::Index(i)),
+                _ => path.push(PathSegment::Key(part.to_string())),
+            },
+            IndexStyle::Brackets => {
+                let (name, mut rest) = match part.find('[') {
+                    Some(i) if part.ends_with(']') => part.split_at(i),
+                    _ => (part, ""),
+                };
+                if !name.is_empty() || rest.is_empty() {
+                    path.push(PathSegment::Key(name.to_string()));
+                }
+                while let Some(index) = rest.strip_prefix('[') {
+                    let (index, tail) = index
+                        .split_once(']')
+                        .ok_or_else(|| anyhow!("invalid index in column {:?}", key))?;
+                    let index = index
+                        .parse()
+                        .map_err(|_| anyhow!("invalid index {:?} in column {:?}", index, key))?;
+                    path.push(PathSegment::Index(index));
+                    rest = tail;
+                }
+                if !rest.is_empty() {
+                    bail!("invalid index in column {:?}", key);
+                }
+            }
+        }
+    }
+    for segment in &path {
+        if let PathSegment::Index(i) = segment {
+            if *i > MAX_INDEX {
+                bail!("index {} in column {:?} is too large", i, key);
+            }
+        }
+    }
+    Ok(path)
+}
+
+// nulls are placeholders that a later column may fill in
+fn insert_path(target: &mut Value, path: &[PathSegment], value: Value) -> Result<(), ()> {
+    let Some((segment, rest)) = path.split_first() else {
+        return match target {
+            Value::Null => {
+                *target = value;
+                Ok(())
+            }
+            _ => Err(()),
+        };
+    };
+    match segment {
+        PathSegment::Key(key) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let Value::Object(map) = target else {
+                return Err(());
+            };
+            insert_path(map.entry(key.clone()).or_insert(Value::Null), rest, value)
+        }
+        PathSegment::Index(i) => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let Value::Array(arr) = target else {
+                return Err(());
+            };
+            if arr.len() <= *i {
+                arr.resize(i + 1, Value::Null);
+            }
+            insert_path(&mut arr[*i], rest, value)
+        }
+    }
+}
+
+pub fn parse_rename(value: &str) -> Result<(String, String)> {
+    let (old, new) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("invalid rename {:?}, expect old=new", value))?;
+    Ok((old.to_string(), new.to_string()))
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, desc) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        Ok(SortKey {
+            column: column.to_string(),
+            desc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_filter;
+    use serde_json::json;
+
+    #[test]
+    fn test_transform_records() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Position", "Kit Number"]);
+        let records = vec![
+            json!({"Name": "Szczesny", "Position": "Goalkeeper", "Kit Number": "1"}),
+            json!({"Name": "Chiellini", "Position": "Centre-Back", "Kit Number": "3"}),
+            json!({"Name": "Buffon", "Position": "Goalkeeper", "Kit Number": "77"}),
+            json!({"Name": "Perin", "Position": "Goalkeeper", "Kit Number": "37"}),
+        ];
+        let opts = CsvTransformOpts {
+            select: vec!["Kit Number".into(), "Name".into()],
+            rename: vec![parse_rename("Kit Number=kit")?],
+            filter: Some(parse_filter(r#"Position == "Goalkeeper""#)?),
+            sort_by: vec!["Kit Number:desc".parse()?],
+            ..Default::default()
+        };
+        let ret = transform_records(records.into_iter().map(Ok), &headers, &opts)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            ret,
+            vec![
+                json!({"kit": "77", "Name": "Buffon"}),
+                json!({"kit": "37", "Name": "Perin"}),
+                json!({"kit": "1", "Name": "Szczesny"}),
+            ]
+        );
+        let keys: Vec<_> = ret[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["kit", "Name"]);
+
+        let opts = CsvTransformOpts {
+            exclude: vec!["Age".into()],
+            ..Default::default()
+        };
+        assert!(transform_records(std::iter::empty(), &headers, &opts).is_err());
+
+        let bad = [
+            CsvTransformOpts {
+                filter: Some(parse_filter(r#"Postion == "Goalkeeper""#)?),
+                ..Default::default()
+            },
+            CsvTransformOpts {
+                rename: vec![parse_rename("Name=Position")?],
+                ..Default::default()
+            },
+            CsvTransformOpts {
+                rename: vec![parse_rename("Name=a")?, parse_rename("Position=a")?],
+                ..Default::default()
+            },
+        ];
+        for opts in &bad {
+            assert!(transform_records(std::iter::empty(), &headers, opts).is_err());
+        }
+        let opts = CsvTransformOpts {
+            rename: vec![
+                parse_rename("Name=Position")?,
+                parse_rename("Position=Name")?,
+            ],
+            ..Default::default()
+        };
+        assert!(transform_records(std::iter::empty(), &headers, &opts).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten() -> Result<()> {
+        let record = json!({"id": "1", "address.city": "Turin", "address.zip": "10151", "tags[1]": "b", "tags[0]": "a"});
+        assert_eq!(
+            unflatten(record, ".", IndexStyle::Brackets)?,
+            json!({"id": "1", "address": {"city": "Turin", "zip": "10151"}, "tags": ["a", "b"]})
+        );
+
+        let record = json!({"players/0/name": "Buffon", "players/2/name": "Perin"});
+        assert_eq!(
+            unflatten(record, "/", IndexStyle::Separator)?,
+            json!({"players": [{"name": "Buffon"}, null, {"name": "Perin"}]})
+        );
+
+        let record = json!({"address": "Turin", "address.city": "Turin"});
+        assert!(unflatten(record, ".", IndexStyle::Brackets).is_err());
+        let record = json!({"tags[x]": "a"});
+        assert!(unflatten(record, ".", IndexStyle::Brackets).is_err());
+        Ok(())
+    }
+}
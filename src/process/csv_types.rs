@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::{Number, Value};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Date,
+}
+
+/// Turns raw CSV cells into typed JSON values, either by inference or by a per-column override.
+/// Dates are ISO 8601 unless a `--date-format` is given.
+#[derive(Debug, Clone, Default)]
+pub struct CellParser {
+    infer: bool,
+    overrides: HashMap<String, ColumnType>,
+    date_format: Option<String>,
+}
+
+impl CellParser {
+    pub fn new(infer: bool, overrides: HashMap<String, ColumnType>) -> Self {
+        Self {
+            infer,
+            overrides,
+            date_format: None,
+        }
+    }
+
+    /// Also accept dates in this strftime format, e.g. "%b %d, %Y". They are written as ISO 8601.
+    pub fn with_date_format(mut self, format: Option<String>) -> Self {
+        self.date_format = format;
+        self
+    }
+
+    pub fn parse(&self, column: &str, cell: &str) -> Result<Value> {
+        let custom_date = self
+            .date_format
+            .as_deref()
+            .and_then(|f| parse_date(cell, f));
+        match self.overrides.get(column) {
+            Some(ColumnType::Date) if custom_date.is_some() => Ok(custom_date.into()),
+            Some(ColumnType::Date) => parse_as(cell, ColumnType::Date).map_err(|e| {
+                anyhow!(
+                    "column {:?}: {}, expect an ISO 8601 date or pass --date-format",
+                    column,
+                    e
+                )
+            }),
+            Some(ty) => parse_as(cell, *ty).map_err(|e| anyhow!("column {:?}: {}", column, e)),
+            None if self.infer && custom_date.is_some() => Ok(custom_date.into()),
+            None if self.infer => Ok(infer_value(cell)),
+            None => Ok(Value::String(cell.to_string())),
+        }
+    }
+}
+
+// a date in a custom format, normalized to ISO 8601
+fn parse_date(cell: &str, format: &str) -> Option<String> {
+    if let Ok(dt) = NaiveDateTime::parse_from_str(cell, format) {
+        Some(dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+    } else {
+        let date = NaiveDate::parse_from_str(cell, format).ok()?;
+        Some(date.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// Parse a `--types` spec such as `Kit Number=int,DOB=date`.
+pub fn parse_types(spec: &str) -> Result<HashMap<String, ColumnType>> {
+    spec.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|pair| {
+            let (column, ty) = pair
+                .rsplit_once('=')
+                .ok_or_else(|| anyhow!("invalid type spec {:?}, expect column=type", pair))?;
+            Ok((column.trim().to_string(), ty.trim().parse()?))
+        })
+        .collect()
+}
+
+/// Detect the narrowest type a cell fits in. Empty cells are null.
+pub fn infer_type(cell: &str) -> Option<ColumnType> {
+    if cell.is_empty() {
+        None
+    } else if parse_int(cell).is_some() {
+        Some(ColumnType::Int)
+    } else if parse_float(cell).is_some() {
+        Some(ColumnType::Float)
+    } else if parse_bool(cell).is_some() {
+        Some(ColumnType::Bool)
+    } else if is_date(cell) {
+        Some(ColumnType::Date)
+    } else {
+        Some(ColumnType::String)
+    }
+}
+
+pub fn infer_value(cell: &str) -> Value {
+    match infer_type(cell) {
+        None => Value::Null,
+        Some(ty) => parse_as(cell, ty).unwrap_or_else(|_| Value::String(cell.to_string())),
+    }
+}
+
+/// Parse a cell as the given type. Empty cells are null unless the column is a string.
+pub fn parse_as(cell: &str, ty: ColumnType) -> Result<Value> {
+    if cell.is_empty() && ty != ColumnType::String {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        ColumnType::String => Some(Value::String(cell.to_string())),
+        ColumnType::Int => parse_int(cell).map(Value::from),
+        ColumnType::Float => parse_float(cell)
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        ColumnType::Bool => parse_bool(cell).map(Value::Bool),
+        ColumnType::Date => is_date(cell).then(|| Value::String(cell.to_string())),
+    };
+    value.ok_or_else(|| anyhow!("cannot parse {:?} as {}", cell, ty))
+}
+
+fn parse_int(cell: &str) -> Option<i64> {
+    if has_leading_zero(cell) {
+        return None;
+    }
+    cell.parse().ok()
+}
+
+fn parse_float(cell: &str) -> Option<f64> {
+    // f64::from_str also accepts "inf" and "NaN", which are not numbers in JSON
+    if has_leading_zero(cell)
+        || !cell.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.')
+    {
+        return None;
+    }
+    cell.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+// numbers with leading zeros (zip codes, ids) are kept as strings
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
+
+fn parse_bool(cell: &str) -> Option<bool> {
+    match cell.to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_date(cell: &str) -> bool {
+    NaiveDate::parse_from_str(cell, "%Y-%m-%d").is_ok()
+        || NaiveDateTime::parse_from_str(cell, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        || NaiveDateTime::parse_from_str(cell, "%Y-%m-%d %H:%M:%S%.f").is_ok()
+        || DateTime::parse_from_rfc3339(cell).is_ok()
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "string" | "str" => Ok(ColumnType::String),
+            "int" | "integer" => Ok(ColumnType::Int),
+            "float" | "number" => Ok(ColumnType::Float),
+            "bool" | "boolean" => Ok(ColumnType::Bool),
+            "date" | "datetime" => Ok(ColumnType::Date),
+            v => Err(anyhow!("unknown column type {:?}", v)),
+        }
+    }
+}
+
+impl TryFrom<String> for ColumnType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+            ColumnType::Date => "date",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_value() {
+        assert_eq!(infer_value("10"), json!(10));
+        assert_eq!(infer_value("-3.5"), json!(-3.5));
+        assert_eq!(infer_value("TRUE"), json!(true));
+        assert_eq!(infer_value(""), Value::Null);
+        assert_eq!(infer_value("007"), json!("007"));
+        assert_eq!(infer_value("NaN"), json!("NaN"));
+        assert_eq!(infer_value("1990-04-18"), json!("1990-04-18"));
+        assert_eq!(infer_type("1990-04-18"), Some(ColumnType::Date));
+        assert_eq!(infer_type("Apr 18, 1990 (29)"), Some(ColumnType::String));
+    }
+
+    #[test]
+    fn test_cell_parser() -> Result<()> {
+        let types = parse_types("Kit Number=int, DOB=date")?;
+        let parser = CellParser::new(false, types);
+        assert_eq!(parser.parse("Kit Number", "10")?, json!(10));
+        assert_eq!(parser.parse("Name", "10")?, json!("10"));
+        assert!(parser.parse("DOB", "Apr 18, 1990 (29)").is_err());
+        assert!(parse_types("Kit Number:int").is_err());
+        assert!(parse_types("Kit Number=decimal").is_err());
+
+        let parser = CellParser::new(true, parse_types("DOB=date")?)
+            .with_date_format(Some("%b %d, %Y".into()));
+        assert_eq!(parser.parse("DOB", "Apr 18, 1990")?, json!("1990-04-18"));
+        assert_eq!(parser.parse("DOB", "1990-04-18")?, json!("1990-04-18"));
+        assert_eq!(parser.parse("Signed", "Jul 1, 2018")?, json!("2018-07-01"));
+        assert!(parser.parse("DOB", "Apr 18, 1990 (29)").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, path::Path};
+
+use crate::{
+    build_reader, get_content, get_reader, parse_as, read_headers, ColumnType, CsvReadOpts,
+};
+
+/// A schema file (YAML, JSON or TOML) describing the columns a CSV file must have.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    /// Reject columns that are not declared in the schema
+    #[serde(default)]
+    pub strict: bool,
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    pub pattern: Option<String>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<String>>,
+    #[serde(default)]
+    pub unique: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Line in the CSV file, `None` for problems with the header
+    pub line: Option<u64>,
+    /// Empty for problems with the whole row
+    pub column: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) if self.column.is_empty() => write!(f, "line {}: {}", line, self.message),
+            Some(line) => write!(
+                f,
+                "line {}, column {:?}: {}",
+                line, self.column, self.message
+            ),
+            None => write!(f, "header, column {:?}: {}", self.column, self.message),
+        }
+    }
+}
+
+pub fn load_schema(path: &str) -> Result<Schema> {
+    let content = String::from_utf8(get_content(path)?)?;
+    let schema = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&content)?,
+        // YAML is a superset of JSON
+        _ => serde_yaml::from_str(&content)?,
+    };
+    Ok(schema)
+}
+
+pub fn process_csv_validate(input: &str, opts: &CsvReadOpts, schema: &str) -> Result<()> {
+    let schema = load_schema(schema)?;
+    let violations = validate(build_reader(get_reader(input)?, opts)?, opts, &schema)?;
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        bail!("{} violations found in {}", violations.len(), input);
+    }
+    Ok(())
+}
+
+struct ColumnCheck<'a> {
+    schema: &'a ColumnSchema,
+    index: usize,
+    pattern: Option<Regex>,
+    // value -> line it was first seen on
+    seen: HashMap<String, u64>,
+}
+
+pub fn validate<R: std::io::Read>(
+    mut reader: csv::Reader<R>,
+    opts: &CsvReadOpts,
+    schema: &Schema,
+) -> Result<Vec<Violation>> {
+    let headers = read_headers(&mut reader, opts)?;
+    let mut violations = Vec::new();
+    let mut header_violation = |column: &str, message: String| {
+        violations.push(Violation {
+            line: None,
+            column: column.to_string(),
+            message,
+        })
+    };
+
+    let mut checks = Vec::new();
+    for column in &schema.columns {
+        match headers.iter().position(|h| h == column.name) {
+            Some(index) => checks.push(ColumnCheck {
+                schema: column,
+                index,
+                pattern: column.pattern.as_deref().map(Regex::new).transpose()?,
+                seen: HashMap::new(),
+            }),
+            None if column.required => header_violation(&column.name, "missing column".into()),
+            None => {}
+        }
+    }
+    if schema.strict {
+        for header in headers.iter() {
+            if !schema.columns.iter().any(|c| c.name == header) {
+                header_violation(header, "column is not declared in the schema".into());
+            }
+        }
+    }
+
+    for result in reader.records() {
+        // a malformed row is a violation like any other, so the rest of the file is still checked
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => match e.kind() {
+                csv::ErrorKind::UnequalLengths {
+                    pos,
+                    expected_len,
+                    len,
+                } => {
+                    violations.push(Violation {
+                        line: pos.as_ref().map(|p| p.line()),
+                        column: String::new(),
+                        message: format!("expected {} fields, found {}", expected_len, len),
+                    });
+                    continue;
+                }
+                csv::ErrorKind::Utf8 { pos, err } => {
+                    violations.push(Violation {
+                        line: pos.as_ref().map(|p| p.line()),
+                        column: String::new(),
+                        message: format!("invalid UTF-8 in field {}", err.field() + 1),
+                    });
+                    continue;
+                }
+                _ => return Err(e.into()),
+            },
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        for check in &mut checks {
+            let cell = record.get(check.index).unwrap_or_default();
+            for message in check.check(cell, line) {
+                violations.push(Violation {
+                    line: Some(line),
+                    column: check.schema.name.clone(),
+                    message,
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+impl ColumnCheck<'_> {
+    fn check(&mut self, cell: &str, line: u64) -> Vec<String> {
+        let schema = self.schema;
+        let mut messages = Vec::new();
+        if cell.is_empty() {
+            if !schema.nullable {
+                messages.push("value is required".to_string());
+            }
+            return messages;
+        }
+        if let Some(ty) = schema.ty {
+            if let Err(e) = parse_as(cell, ty) {
+                messages.push(e.to_string());
+            }
+        }
+        let typed = messages.is_empty();
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(cell) {
+                messages.push(format!("{:?} does not match {:?}", cell, pattern.as_str()));
+            }
+        }
+        if let Some(values) = &schema.values {
+            if !values.iter().any(|v| v == cell) {
+                messages.push(format!("{:?} is not one of {:?}", cell, values));
+            }
+        }
+        // a cell of the wrong type is already reported
+        if typed && (schema.min.is_some() || schema.max.is_some()) {
+            match cell.parse::<f64>() {
+                Ok(n) if schema.min.is_some_and(|min| n < min) => messages.push(format!(
+                    "{} is less than {}",
+                    cell,
+                    schema.min.unwrap_or_default()
+                )),
+                Ok(n) if schema.max.is_some_and(|max| n > max) => messages.push(format!(
+                    "{} is greater than {}",
+                    cell,
+                    schema.max.unwrap_or_default()
+                )),
+                Ok(_) => {}
+                Err(_) => messages.push(format!("{:?} is not a number", cell)),
+            }
+        }
+        if schema.unique {
+            match self.seen.get(cell) {
+                Some(first) => messages.push(format!("duplicate of {:?} on line {}", cell, first)),
+                None => {
+                    self.seen.insert(cell.to_string(), line);
+                }
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+strict: true
+columns:
+  - name: Name
+    nullable: false
+    unique: true
+  - name: Position
+    enum: [Goalkeeper, Centre-Back]
+  - name: Kit Number
+    type: int
+    min: 1
+    max: 99
+  - name: DOB
+    pattern: '^\d{4}-\d{2}-\d{2}$'
+  - name: Age
+    required: false
+"#;
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let schema: Schema = serde_yaml::from_str(SCHEMA)?;
+        let data = "\
+Name,Position,Kit Number,DOB,Club
+Buffon,Goalkeeper,77,1978-01-28,Juventus
+Buffon,Striker,100,\"Jan 28, 1978\",Juventus
+Chiellini,Centre-Back
+,Centre-Back,x,,Juventus
+";
+        let opts = CsvReadOpts::default();
+        let violations = validate(build_reader(data.as_bytes(), &opts)?, &opts, &schema)?;
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                r#"header, column "Club": column is not declared in the schema"#,
+                r#"line 3, column "Name": duplicate of "Buffon" on line 2"#,
+                r#"line 3, column "Position": "Striker" is not one of ["Goalkeeper", "Centre-Back"]"#,
+                r#"line 3, column "Kit Number": 100 is greater than 99"#,
+                r#"line 3, column "DOB": "Jan 28, 1978" does not match "^\\d{4}-\\d{2}-\\d{2}$""#,
+                r#"line 4: expected 5 fields, found 2"#,
+                r#"line 5, column "Name": value is required"#,
+                r#"line 5, column "Kit Number": cannot parse "x" as int"#,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_rejects_unknown_type() {
+        let schema = "columns:\n  - name: Kit Number\n    type: decimal\n";
+        assert!(serde_yaml::from_str::<Schema>(schema).is_err());
+    }
+}
@@ -1,51 +1,723 @@
-use rand::seq::SliceRandom;
-// use zxcvbn::zxcvbn;
-
-const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
-const NUMBER: &[u8] = b"123456789";
-const SYMBOL: &[u8] = b"!@#$%^&*_";
-
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
+use anyhow::{anyhow, bail};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, io::Write};
+use zxcvbn::zxcvbn;
+
+use crate::GenPassFormat;
+
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const NUMBER: &str = "0123456789";
+const SYMBOL: &str = "!@#$%^&*_";
+// look-alikes left out of the built-in sets unless asked for
+const AMBIGUOUS: &str = "IOlo0";
+// the diceware list `--passphrase` draws from unless given `--wordlist`, a copy of
+// https://www.eff.org/files/2016/07/18/eff_large_wordlist.txt
+const EFF_WORDLIST: &str = include_str!("../../assets/eff_large_wordlist.txt");
+// one word per roll of five dice
+const WORDLIST_LEN: usize = 7776;
+// passwords tried before giving up on the score rule, or on the repeat rule when the
+// required characters leave too little to separate them
+const MAX_ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharKind {
+    #[serde(alias = "uppercase")]
+    Upper,
+    #[serde(alias = "lowercase")]
+    Lower,
+    #[serde(alias = "digits", alias = "number", alias = "numbers")]
+    Digit,
+    #[serde(alias = "symbols")]
+    Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharClass {
+    pub chars: Vec<char>,
+    /// How many characters of the password must come from this class
+    pub min: usize,
+    /// Set for the built-in classes and for classes added by `require`
+    pub kind: Option<CharKind>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordRules {
+    pub length: usize,
+    pub classes: Vec<CharClass>,
+    pub exclude: Vec<char>,
+    /// Longest run of one character, 1 means never the same character twice in a row
+    pub max_repeat: Option<usize>,
+    /// Fixed start of the password, counted in `length`
+    pub prefix: String,
+    /// Minimum zxcvbn score, 0 to 4
+    pub min_score: Option<u8>,
+}
+
+/// Which built-in classes a password draws from, e.g.
+/// `BuiltinClasses { symbol: false, ..Default::default() }`. All four by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinClasses {
+    pub upper: bool,
+    pub lower: bool,
+    pub digit: bool,
+    pub symbol: bool,
+    /// Keep look-alikes such as 0 and O
+    pub ambiguous: bool,
+}
+
+impl Default for BuiltinClasses {
+    fn default() -> Self {
+        Self {
+            upper: true,
+            lower: true,
+            digit: true,
+            symbol: true,
+            ambiguous: false,
+        }
+    }
+}
+
+impl PasswordRules {
+    /// The chosen built-in classes, each required once.
+    pub fn new(length: usize, builtin: BuiltinClasses) -> Self {
+        let classes = [
+            (builtin.upper, UPPER, CharKind::Upper),
+            (builtin.lower, LOWER, CharKind::Lower),
+            (builtin.digit, NUMBER, CharKind::Digit),
+            (builtin.symbol, SYMBOL, CharKind::Symbol),
+        ]
+        .into_iter()
+        .filter(|(enabled, _, _)| *enabled)
+        .map(|(_, chars, kind)| CharClass {
+            chars: chars
+                .chars()
+                .filter(|c| builtin.ambiguous || !AMBIGUOUS.contains(*c))
+                .collect(),
+            min: 1,
+            kind: Some(kind),
+        })
+        .collect();
+        Self {
+            length,
+            classes,
+            ..Default::default()
+        }
+    }
+
+    /// One class per charset such as "a-z0-9", each required once.
+    pub fn from_charsets(length: usize, charsets: &[Vec<char>]) -> Self {
+        let classes = charsets
+            .iter()
+            .map(|chars| CharClass {
+                chars: chars.clone(),
+                min: 1,
+                kind: None,
+            })
+            .collect();
+        Self {
+            length,
+            classes,
+            ..Default::default()
+        }
+    }
+
+    /// Use `symbols` instead of the built-in symbol set.
+    pub fn set_symbols(&mut self, symbols: &str) {
+        for class in &mut self.classes {
+            if class.kind == Some(CharKind::Symbol) {
+                class.chars = symbols.chars().collect();
+            }
+        }
+    }
+
+    /// Require at least `min` characters of `kind`. Changes the built-in class of that kind if
+    /// there is one, otherwise adds a class of the matching characters of the other classes.
+    pub fn require(&mut self, kind: CharKind, min: usize) -> anyhow::Result<()> {
+        if let Some(class) = self.classes.iter_mut().find(|c| c.kind == Some(kind)) {
+            class.min = min;
+            return Ok(());
+        }
+        let mut chars: Vec<char> = self
+            .classes
+            .iter()
+            .flat_map(|c| &c.chars)
+            .filter(|c| kind.matches(**c))
+            .copied()
+            .collect();
+        chars.sort_unstable();
+        chars.dedup();
+        if chars.is_empty() && min > 0 {
+            bail!("no {} to require {} of", kind, min);
+        }
+        self.classes.push(CharClass {
+            chars,
+            min,
+            kind: Some(kind),
+        });
+        Ok(())
+    }
+}
+
+/// A generated password and how strong it is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GeneratedPassword {
+    pub password: String,
+    /// zxcvbn score, 0 to 4
+    pub score: u8,
+    /// zxcvbn estimate for an offline attack on a slow hash, 10k guesses per second
+    pub crack_time: String,
+    /// Bits of entropy of the generator, not of this particular password
+    pub entropy: f64,
+}
+
+impl GeneratedPassword {
+    pub fn new(password: String, entropy: f64) -> Self {
+        let estimate = zxcvbn(&password, &[]);
+        let crack_time = estimate
+            .crack_times()
+            .offline_slow_hashing_1e4_per_second()
+            .to_string();
+        Self {
+            password,
+            score: estimate.score().into(),
+            crack_time,
+            entropy: (entropy * 10.0).round() / 10.0,
+        }
+    }
+}
+
+impl PasswordRules {
+    /// Entropy in bits of the random part, counting every character as drawn from the whole
+    /// pool, less the previous character when repeats are not allowed. Required classes and
+    /// longer repeat limits and the score rule make it a slight overestimate.
+    pub fn entropy(&self) -> f64 {
+        let (_, chars) = self.pool();
+        let random = self.length.saturating_sub(self.prefix.chars().count());
+        let choices = chars.len().max(1) as f64;
+        match (self.max_repeat, random) {
+            (_, 0) => 0.0,
+            (Some(1), random) => {
+                choices.log2() + (random - 1) as f64 * (choices - 1.0).max(1.0).log2()
+            }
+            (_, random) => random as f64 * choices.log2(),
+        }
+    }
+
+    // classes and the union of their characters, with exclusions removed
+    fn pool(&self) -> (Vec<CharClass>, Vec<char>) {
+        let classes: Vec<CharClass> = self
+            .classes
+            .iter()
+            .map(|class| CharClass {
+                chars: class
+                    .chars
+                    .iter()
+                    .filter(|c| !self.exclude.contains(c))
+                    .copied()
+                    .collect(),
+                ..class.clone()
+            })
+            .collect();
+        let mut chars: Vec<char> = classes.iter().flat_map(|c| c.chars.clone()).collect();
+        chars.sort_unstable();
+        chars.dedup();
+        (classes, chars)
+    }
+}
+
+impl CharKind {
+    fn matches(self, c: char) -> bool {
+        match self {
+            CharKind::Upper => c.is_uppercase(),
+            CharKind::Lower => c.is_lowercase(),
+            CharKind::Digit => c.is_ascii_digit(),
+            CharKind::Symbol => !c.is_alphanumeric(),
+        }
+    }
+}
+
+impl std::fmt::Display for CharKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CharKind::Upper => "uppercase letters",
+            CharKind::Lower => "lowercase letters",
+            CharKind::Digit => "digits",
+            CharKind::Symbol => "symbols",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Expand a charset spec such as "a-z0-9_". A '-' at either end is literal.
+pub fn parse_charset(spec: &str) -> anyhow::Result<Vec<char>> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut set = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars.get(i + 1..i + 3) {
+            Some(&['-', end]) => {
+                let start = chars[i];
+                if start > end {
+                    bail!("invalid range {}-{} in charset {:?}", start, end, spec);
+                }
+                set.extend(start..=end);
+                i += 3;
+            }
+            _ => {
+                set.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    let mut seen = Vec::with_capacity(set.len());
+    set.retain(|c| {
+        let new = !seen.contains(c);
+        seen.push(*c);
+        new
+    });
+    if set.is_empty() {
+        bail!("charset {:?} is empty", spec);
+    }
+    Ok(set)
+}
+
+pub fn process_genpass(rules: &PasswordRules) -> anyhow::Result<String> {
+    let (classes, chars) = rules.pool();
+    if chars.is_empty() {
+        bail!("no characters left to build a password from");
+    }
+    if let Some(class) = classes.iter().find(|c| c.min > 0 && c.chars.is_empty()) {
+        match class.kind {
+            Some(kind) => bail!("every one of the required {} is excluded", kind),
+            None => bail!("every character of a required charset is excluded"),
+        }
+    }
+    let required = rules.prefix.chars().count() + classes.iter().map(|c| c.min).sum::<usize>();
+    if rules.length < required {
+        bail!(
+            "length {} is shorter than the {} required characters",
+            rules.length,
+            required
+        );
+    }
+    match rules.max_repeat {
+        Some(0) => bail!("max repeat must be at least 1"),
+        Some(max) if chars.len() < 2 && rules.length - rules.prefix.chars().count() > max => {
+            bail!("cannot avoid repeated characters with a single character to choose from")
+        }
+        _ => {}
+    }
+    if rules.min_score.is_some_and(|score| score > 4) {
+        bail!("zxcvbn scores range from 0 to 4");
+    }
+
+    let prefix: Vec<char> = rules.prefix.chars().collect();
+    if let Some(max) = rules
+        .max_repeat
+        .filter(|max| longest_run(&rules.prefix) > *max)
+    {
+        bail!(
+            "prefix {:?} repeats a character more than {} times in a row",
+            rules.prefix,
+            max
+        );
+    }
+
     let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
+    let mut runs_met = false;
+    for _ in 0..MAX_ATTEMPTS {
+        // each character with the class it was drawn for, `None` for the rest
+        let mut password: Vec<(char, Option<usize>)> = Vec::with_capacity(rules.length);
+        for (i, class) in classes.iter().enumerate() {
+            for _ in 0..class.min {
+                password.push((*class.chars.choose(&mut rng).expect("not empty"), Some(i)));
+            }
+        }
+        for _ in password.len() + prefix.len()..rules.length {
+            password.push((*chars.choose(&mut rng).expect("not empty"), None));
+        }
+        password.shuffle(&mut rng);
+        let runs_ok = rules
+            .max_repeat
+            .is_none_or(|max| limit_runs(&mut password, &prefix, &classes, &chars, max, &mut rng));
+        if !runs_ok {
+            continue;
+        }
+        runs_met = true;
 
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("not empty"));
+        let password: String = prefix
+            .iter()
+            .copied()
+            .chain(password.into_iter().map(|(c, _)| c))
+            .collect();
+        if rules
+            .min_score
+            .is_none_or(|min| u8::from(zxcvbn(&password, &[]).score()) >= min)
+        {
+            return Ok(password);
+        }
     }
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("not empty"));
+    match (runs_met, rules.max_repeat) {
+        (false, Some(max)) => bail!(
+            "cannot keep the required characters to runs of {} in {} characters",
+            max,
+            rules.length
+        ),
+        _ => bail!(
+            "no password reached zxcvbn score {} after {} attempts",
+            rules.min_score.unwrap_or_default(),
+            MAX_ATTEMPTS
+        ),
     }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("not empty"));
+}
+
+// Break up runs longer than `max` after the shuffle. A character that would extend a run is
+// redrawn from the pool, or, if a class requires it, swapped with a later different character
+// or redrawn from its own class, so the class minimums still hold. Returns false only when the
+// rest of the password is one required character that has nothing left to separate it.
+fn limit_runs(
+    password: &mut [(char, Option<usize>)],
+    prefix: &[char],
+    classes: &[CharClass],
+    chars: &[char],
+    max: usize,
+    rng: &mut impl Rng,
+) -> bool {
+    for i in 0..password.len() {
+        let c = password[i].0;
+        let run = prefix
+            .iter()
+            .copied()
+            .chain(password[..i].iter().map(|(c, _)| *c))
+            .rev()
+            .take_while(|p| *p == c)
+            .count();
+        if run < max {
+            continue;
+        }
+        let other =
+            |set: &[char]| -> Vec<char> { set.iter().copied().filter(|o| *o != c).collect() };
+        let redraw = match password[i].1 {
+            None => other(chars).choose(rng).copied(),
+            Some(class) => {
+                let later: Vec<usize> = (i + 1..password.len())
+                    .filter(|j| password[*j].0 != c)
+                    .collect();
+                match later.choose(rng) {
+                    Some(j) => {
+                        password.swap(i, *j);
+                        continue;
+                    }
+                    None => other(&classes[class].chars).choose(rng).copied(),
+                }
+            }
+        };
+        match redraw {
+            Some(redraw) => password[i].0 = redraw,
+            None => return false,
+        }
     }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("not empty"));
+    true
+}
+
+fn longest_run(password: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut last = None;
+    for c in password.chars() {
+        run = if last == Some(c) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        last = Some(c);
     }
+    longest
+}
 
-    for _ in 0..length - password.len() as u8 {
-        password.push(*chars.choose(&mut rng).expect("not empty"));
+/// Read a diceware wordlist from `path`, or use the bundled EFF large wordlist.
+pub fn load_wordlist(path: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let Some(path) = path else {
+        return parse_wordlist(EFF_WORDLIST);
+    };
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read wordlist {}: {}", path, e))?;
+    parse_wordlist(&content).map_err(|e| anyhow!("invalid wordlist {}: {}", path, e))
+}
+
+/// One `11111<tab>word` line per roll of five dice, every roll once and in order.
+pub fn parse_wordlist(content: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::with_capacity(WORDLIST_LEN);
+    let mut seen = HashSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let expected = dice_roll(i);
+        let (roll, word) = line.split_once('\t').unwrap_or((line, ""));
+        if roll != expected {
+            bail!("line {}: expect roll {}, found {:?}", i + 1, expected, roll);
+        }
+        if word.is_empty() || !seen.insert(word) {
+            bail!("line {}: missing or repeated word {:?}", i + 1, word);
+        }
+        words.push(word.to_string());
+    }
+    if words.len() != WORDLIST_LEN {
+        bail!("expect {} words, found {}", WORDLIST_LEN, words.len());
     }
+    Ok(words)
+}
 
-    password.shuffle(&mut rng);
+// the i-th roll of five dice in order: 0 -> "11111", 7775 -> "66666"
+fn dice_roll(i: usize) -> String {
+    (0..5)
+        .rev()
+        .map(|place| char::from(b'1' + (i / 6usize.pow(place) % 6) as u8))
+        .collect()
+}
 
-    let password = String::from_utf8(password)?;
+/// Write passwords one per line, as a JSON array or as CSV with their score, crack time and entropy.
+pub fn write_passwords(
+    writer: &mut dyn Write,
+    passwords: &[GeneratedPassword],
+    format: GenPassFormat,
+) -> anyhow::Result<()> {
+    match format {
+        GenPassFormat::Plain => {
+            for password in passwords {
+                writeln!(writer, "{}", password.password)?;
+            }
+        }
+        GenPassFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, passwords)?;
+            writeln!(writer)?;
+        }
+        GenPassFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut *writer);
+            for password in passwords {
+                csv_writer.serialize(password)?;
+            }
+            csv_writer.flush()?;
+        }
+    }
+    Ok(())
+}
 
-    // println!("{:?}", password);
+/// Diceware passphrase from `list`, e.g. the EFF large wordlist. `digits` random digits are
+/// appended to random words. Returns the passphrase and its entropy in bits, not counting where the digits landed.
+pub fn process_passphrase(
+    list: &[String],
+    words: u8,
+    separator: &str,
+    capitalize: bool,
+    digits: u8,
+) -> anyhow::Result<(String, f64)> {
+    if words == 0 || list.is_empty() {
+        bail!("a passphrase needs at least one word");
+    }
+    let mut rng = rand::thread_rng();
+    let mut phrase: Vec<String> = (0..words)
+        .map(|_| {
+            let word = list.choose(&mut rng).expect("checked above");
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) if capitalize => first.to_uppercase().chain(chars).collect(),
+                _ => word.to_string(),
+            }
+        })
+        .collect();
+    for _ in 0..digits {
+        let digit = char::from(b'0' + rng.gen_range(0..10));
+        phrase.choose_mut(&mut rng).expect("not empty").push(digit);
+    }
+
+    let entropy = words as f64 * (list.len() as f64).log2() + digits as f64 * 10f64.log2();
+    Ok((phrase.join(separator), entropy))
+}
 
-    // let estimate = zxcvbn(&password, &[]);
-    // eprintln!("strength:{}", estimate.score()); // 3
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(password)
+    #[test]
+    fn test_process_genpass() -> anyhow::Result<()> {
+        let builtin = BuiltinClasses {
+            symbol: false,
+            ..Default::default()
+        };
+        let password = process_genpass(&PasswordRules::new(12, builtin))?;
+        assert_eq!(password.len(), 12);
+        assert!(!password.contains(|c| SYMBOL.contains(c) || AMBIGUOUS.contains(c)));
+        let digits = BuiltinClasses {
+            upper: false,
+            lower: false,
+            digit: true,
+            symbol: false,
+            ambiguous: false,
+        };
+        let rules = PasswordRules::new(1, digits);
+        assert_eq!(process_genpass(&rules)?.len(), 1);
+
+        let none = PasswordRules::new(
+            16,
+            BuiltinClasses {
+                digit: false,
+                ..digits
+            },
+        );
+        assert!(process_genpass(&none).is_err());
+        let short = PasswordRules::new(3, BuiltinClasses::default());
+        assert!(process_genpass(&short).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_password_rules() -> anyhow::Result<()> {
+        let mut rules = PasswordRules::from_charsets(10, &[parse_charset("a-f0-9")?]);
+        rules.require(CharKind::Digit, 4)?;
+        rules.exclude = vec!['0', 'a'];
+        rules.max_repeat = Some(1);
+        for _ in 0..20 {
+            let password = process_genpass(&rules)?;
+            assert_eq!(password.chars().count(), 10);
+            assert!(password.chars().filter(char::is_ascii_digit).count() >= 4);
+            assert!(!password.contains(['0', 'a', 'g']));
+            assert!(password.as_bytes().windows(2).all(|w| w[0] != w[1]));
+        }
+        assert!(rules.require(CharKind::Upper, 1).is_err());
+
+        let builtin = BuiltinClasses {
+            upper: false,
+            lower: false,
+            ambiguous: true,
+            ..Default::default()
+        };
+        let mut rules = PasswordRules::new(8, builtin);
+        rules.set_symbols("-_.");
+        rules.require(CharKind::Digit, 7)?;
+        let password = process_genpass(&rules)?;
+        assert!(password.contains(['-', '_', '.']));
+        rules.exclude = "-_.".chars().collect();
+        assert!(process_genpass(&rules).is_err());
+
+        let mut rules = PasswordRules::from_charsets(4, &[vec!['x']]);
+        rules.max_repeat = Some(1);
+        assert!(process_genpass(&rules).is_err());
+        rules.max_repeat = Some(4);
+        assert_eq!(process_genpass(&rules)?, "xxxx");
+
+        // runs are broken up while building, so tight rules that can be met always are
+        for (length, charset) in [(40, "ab"), (255, "0-9"), (255, "a-z")] {
+            let mut rules = PasswordRules::from_charsets(length, &[parse_charset(charset)?]);
+            rules.max_repeat = Some(1);
+            let password = process_genpass(&rules)?;
+            assert_eq!(password.len(), length);
+            assert!(password.as_bytes().windows(2).all(|w| w[0] != w[1]));
+        }
+        let mut rules = PasswordRules::from_charsets(6, &[vec!['a'], vec!['b']]);
+        rules.classes[0].min = 3;
+        rules.max_repeat = Some(1);
+        rules.prefix = "b".into();
+        assert_eq!(process_genpass(&rules)?, "bababa");
+        rules.prefix = "aa".into();
+        assert!(process_genpass(&rules).is_err());
+
+        let mut rules = PasswordRules::new(14, BuiltinClasses::default());
+        rules.prefix = "ldap-".into();
+        rules.min_score = Some(4);
+        let password = process_genpass(&rules)?;
+        assert!(password.starts_with("ldap-"));
+        assert_eq!(password.len(), 14);
+        rules.length = 8;
+        assert!(process_genpass(&rules).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_passwords() -> anyhow::Result<()> {
+        let rules = PasswordRules::new(16, BuiltinClasses::default());
+        assert!((rules.entropy() - 16.0 * 66f64.log2()).abs() < 1e-9);
+        let mut rules = PasswordRules::from_charsets(40, &[vec!['a', 'b']]);
+        rules.max_repeat = Some(1);
+        assert_eq!(rules.entropy(), 1.0);
+
+        let passwords = vec![GeneratedPassword::new("password".into(), 9.87)];
+        assert_eq!(passwords[0].score, 0);
+        assert_eq!(passwords[0].entropy, 9.9);
+
+        let mut buf = Vec::new();
+        write_passwords(&mut buf, &passwords, GenPassFormat::Csv)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            format!(
+                "password,score,crack_time,entropy\npassword,0,{},9.9\n",
+                passwords[0].crack_time
+            )
+        );
+        let mut buf = Vec::new();
+        write_passwords(&mut buf, &passwords, GenPassFormat::Json)?;
+        let value: serde_json::Value = serde_json::from_slice(&buf)?;
+        assert_eq!(value[0]["password"], "password");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_charset() -> anyhow::Result<()> {
+        assert_eq!(parse_charset("a-e")?, vec!['a', 'b', 'c', 'd', 'e']);
+        assert_eq!(parse_charset("0-2_-")?, vec!['0', '1', '2', '_', '-']);
+        assert_eq!(parse_charset("-ab-")?, vec!['-', 'a', 'b']);
+        assert_eq!(parse_charset("aa")?, vec!['a']);
+        assert!(parse_charset("z-a").is_err());
+        assert!(parse_charset("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_passphrase() -> anyhow::Result<()> {
+        let content: Vec<String> = (0..WORDLIST_LEN)
+            .map(|i| {
+                let roll = dice_roll(i);
+                // letters only, so the digits counted below are the appended ones
+                let word: String = roll.bytes().map(|b| char::from(b - b'1' + b'a')).collect();
+                format!("{}\t{}", roll, word)
+            })
+            .collect();
+        let list = parse_wordlist(&content.join("\n"))?;
+        assert_eq!(list.len(), WORDLIST_LEN);
+        assert_eq!(dice_roll(WORDLIST_LEN - 1), "66666");
+        assert!(parse_wordlist(&content[1..].join("\n")).is_err());
+        assert!(parse_wordlist(&content[..WORDLIST_LEN - 1].join("\n")).is_err());
+        let mut repeated = content.clone();
+        repeated[1] = "11112\taaaaa".to_string();
+        assert!(parse_wordlist(&repeated.join("\n")).is_err());
+        assert!(load_wordlist(Some("fixtures/missing_wordlist.txt")).is_err());
+
+        let (phrase, entropy) = process_passphrase(&list, 6, " ", true, 2)?;
+        let words: Vec<&str> = phrase.split(' ').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| w.starts_with(char::is_uppercase)));
+        assert_eq!(phrase.chars().filter(char::is_ascii_digit).count(), 2);
+        assert!((entropy - 84.2).abs() < 0.1);
+
+        assert!(process_passphrase(&list, 0, " ", false, 0).is_err());
+
+        // user lists may start words with any character
+        let list = vec!["éclair".to_string(), "ßtraße".to_string()];
+        let (phrase, _) = process_passphrase(&list, 8, " ", true, 0)?;
+        assert!(phrase.split(' ').all(|w| w == "Éclair" || w == "SStraße"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eff_wordlist() -> anyhow::Result<()> {
+        use sha2::{Digest, Sha256};
+
+        // SHA-256 of the bundled copy, update it only after comparing with the published file
+        assert_eq!(
+            hex::encode(Sha256::digest(EFF_WORDLIST)),
+            "58389de2ffd827ebd50d5de8858a6671e58bbf838de8494c39e179620e3244f6"
+        );
+        let list = load_wordlist(None)?;
+        assert_eq!(list.len(), WORDLIST_LEN);
+        assert_eq!((list[0].as_str(), list[7775].as_str()), ("abacus", "zoom"));
+        Ok(())
+    }
 }
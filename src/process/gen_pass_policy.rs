@@ -0,0 +1,140 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{get_content, BuiltinClasses, CharKind, PasswordRules};
+
+/// A site password policy, stored under its name in a TOML or YAML policy file.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// Character classes the password must include at least once: upper, lower, digit, symbol
+    #[serde(default = "all_kinds")]
+    pub require: Vec<CharKind>,
+    /// Character classes the password may draw from, all four if unset. Must include `require`
+    pub allow: Option<Vec<CharKind>>,
+    /// Symbols to use instead of the built-in set
+    pub symbols: Option<String>,
+    #[serde(default)]
+    pub forbidden: String,
+    /// Longest run of one character
+    pub max_repeats: Option<usize>,
+    #[serde(default)]
+    pub prefix: String,
+    /// Minimum zxcvbn score, 0 to 4
+    pub min_score: Option<u8>,
+}
+
+fn all_kinds() -> Vec<CharKind> {
+    vec![
+        CharKind::Upper,
+        CharKind::Lower,
+        CharKind::Digit,
+        CharKind::Symbol,
+    ]
+}
+
+impl PasswordPolicy {
+    /// Rules for a password of `length`, moved into the policy's length range.
+    pub fn rules(&self, length: usize) -> Result<PasswordRules> {
+        let min = self.min_length.unwrap_or(0);
+        let max = self.max_length.unwrap_or(usize::MAX);
+        if min > max {
+            bail!("policy min_length {} is above max_length {}", min, max);
+        }
+        if let Some(c) = self.prefix.chars().find(|c| self.forbidden.contains(*c)) {
+            bail!("policy prefix {:?} has the forbidden {:?}", self.prefix, c);
+        }
+        let allow = self.allow.clone().unwrap_or_else(all_kinds);
+        if let Some(kind) = self.require.iter().find(|kind| !allow.contains(kind)) {
+            bail!("policy requires {} but does not allow them", kind);
+        }
+        let builtin = BuiltinClasses {
+            upper: allow.contains(&CharKind::Upper),
+            lower: allow.contains(&CharKind::Lower),
+            digit: allow.contains(&CharKind::Digit),
+            symbol: allow.contains(&CharKind::Symbol),
+            ambiguous: false,
+        };
+        let mut rules = PasswordRules::new(length.clamp(min, max), builtin);
+        for kind in allow.iter().filter(|kind| !self.require.contains(kind)) {
+            rules.require(*kind, 0)?;
+        }
+        if let Some(symbols) = &self.symbols {
+            rules.set_symbols(symbols);
+        }
+        rules.exclude = self.forbidden.chars().collect();
+        rules.max_repeat = self.max_repeats;
+        rules.prefix = self.prefix.clone();
+        rules.min_score = self.min_score;
+        Ok(rules)
+    }
+}
+
+/// Read the policy called `name` from a file of named policies.
+pub fn load_policy(path: &str, name: &str) -> Result<PasswordPolicy> {
+    let content =
+        get_content(path).map_err(|e| anyhow!("cannot read policy file {}: {}", path, e))?;
+    let content = String::from_utf8(content)?;
+    let mut policies: BTreeMap<String, PasswordPolicy> =
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)?,
+            _ => serde_yaml::from_str(&content)?,
+        };
+    policies.remove(name).ok_or_else(|| {
+        let names: Vec<&str> = policies.keys().map(String::as_str).collect();
+        anyhow!(
+            "policy {:?} not found in {}, expect one of: {}",
+            name,
+            path,
+            names.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_genpass;
+
+    #[test]
+    fn test_load_policy() -> Result<()> {
+        let policy = load_policy("fixtures/policies.toml", "corp-ldap")?;
+        assert_eq!(policy.min_length, Some(12));
+        assert_eq!(
+            policy.require,
+            vec![CharKind::Upper, CharKind::Lower, CharKind::Digit]
+        );
+        let rules = policy.rules(8)?;
+        assert_eq!(rules.length, 12);
+        // symbols are allowed but not required
+        let mins: Vec<_> = rules.classes.iter().map(|c| c.min).collect();
+        assert_eq!(mins, vec![1, 1, 1, 0]);
+        let password = process_genpass(&rules)?;
+        assert!(password.starts_with('L'));
+        assert!(!password.contains(['"', '\'', '\\', ' ']));
+
+        let rules = load_policy("fixtures/policies.toml", "legacy-erp")?.rules(16)?;
+        assert_eq!(rules.length, 8);
+        let password = process_genpass(&rules)?;
+        assert!(password.contains(['-', '_', '.']));
+        assert!(!password.contains(char::is_lowercase));
+
+        assert!(load_policy("fixtures/policies.toml", "corp-vpn").is_err());
+
+        let policy = PasswordPolicy {
+            prefix: "L 1".into(),
+            ..load_policy("fixtures/policies.toml", "corp-ldap")?
+        };
+        assert!(policy.rules(16).is_err());
+
+        let policy = PasswordPolicy {
+            allow: Some(vec![CharKind::Lower, CharKind::Digit]),
+            ..load_policy("fixtures/policies.toml", "corp-ldap")?
+        };
+        assert!(policy.rules(16).is_err());
+        Ok(())
+    }
+}
@@ -1,11 +1,48 @@
 mod b64;
+mod convert;
+mod csv_agg;
+mod csv_columnar;
 mod csv_convert;
+mod csv_diff;
+mod csv_encoding;
+mod csv_filter;
+mod csv_join;
+mod csv_mask;
+mod csv_query;
+mod csv_sheet;
+mod csv_show;
+mod csv_split;
+mod csv_stats;
+mod csv_transform;
+mod csv_types;
+mod csv_validate;
 mod gen_pass;
+mod gen_pass_policy;
 mod http_server;
 mod text;
 
 pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use convert::{flatten_records, process_convert, read_records};
+pub use csv_agg::{aggregate_records, process_csv_agg, AggFunc, Aggregate};
+pub use csv_columnar::{infer_schema, write_columnar};
+pub use csv_convert::{build_reader, process_csv, read_headers, record_to_value, write_records};
+pub use csv_diff::{diff_records, process_csv_diff, render_diff, CellChange, CsvDiff, RowChange};
+pub use csv_encoding::{decode_reader, detect_encoding};
+pub use csv_filter::{parse_filter, Filter};
+pub use csv_join::{concat_records, join_records, process_csv_concat, process_csv_join};
+pub use csv_mask::{load_masker, parse_mask, Masker};
+pub use csv_query::{load_table, process_csv_query, run_query, table_name};
+pub use csv_sheet::{is_spreadsheet, process_list_sheets, read_sheet, CellRange};
+pub use csv_show::process_csv_show;
+pub use csv_split::{parse_size, process_csv_split, split_csv, SplitBy};
+pub use csv_stats::process_csv_stats;
+pub use csv_transform::{compare_values, parse_rename, transform_records, SortKey};
+pub use csv_types::{infer_type, infer_value, parse_as, parse_types, CellParser, ColumnType};
+pub use csv_validate::{load_schema, process_csv_validate, validate, Schema, Violation};
+pub use gen_pass::{
+    load_wordlist, parse_charset, parse_wordlist, process_genpass, process_passphrase,
+    write_passwords, BuiltinClasses, CharClass, CharKind, GeneratedPassword, PasswordRules,
+};
+pub use gen_pass_policy::{load_policy, PasswordPolicy};
 pub use http_server::process_http_server;
 pub use text::{process_text_generate, process_text_sign, process_text_verify};
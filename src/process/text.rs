@@ -6,7 +6,7 @@ use anyhow::{Ok, Result};
 use ed25519::Signature;
 use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
 
-use super::process_genpass;
+use super::{process_genpass, BuiltinClasses, PasswordRules};
 
 pub trait TextSign {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>>;
@@ -105,8 +105,15 @@ impl Blake3 {
         Ok(signer)
     }
 
+    /// Keyed hash of `data` as an extendable output reader.
+    pub fn keyed_xof(&self, data: &[u8]) -> blake3::OutputReader {
+        blake3::Hasher::new_keyed(&self.key)
+            .update(data)
+            .finalize_xof()
+    }
+
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let key = process_genpass(&PasswordRules::new(32, BuiltinClasses::default()))?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -236,7 +243,7 @@ mod tests {
     fn test_process_text_verify() -> Result<()> {
         let mut reader = "hello".as_bytes();
         let format = TextSignFormat::Blake3;
-        let sig = "33Ypo4rveYpWmJKAiGnnse-wHQhMVujjmcVkV4Tl43k";
+        let sig = "LURhwV4b23GA5-dQMVj46oVQefsLofakqgKtVknbrUs";
         let sig: Vec<u8> = URL_SAFE_NO_PAD.decode(sig)?;
         let ret = process_text_verify(&mut reader, KEY, &sig, format)?;
         assert!(ret);
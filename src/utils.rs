@@ -1,5 +1,8 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -10,6 +13,42 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     Ok(reader)
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write + Send>> {
+    let writer: Box<dyn Write + Send> = if output == "-" {
+        Box::new(BufWriter::new(std::io::stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+    Ok(writer)
+}
+
+/// Run `f` against the output. A file is written next to its target and only renamed into place
+/// once `f` succeeds, so a failed conversion never leaves a truncated file behind.
+pub fn write_output<F>(output: &str, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Box<dyn Write + Send>) -> Result<()>,
+{
+    if output == "-" {
+        let mut writer = get_writer(output)?;
+        f(&mut writer)?;
+        writer.flush()?;
+        return Ok(());
+    }
+    let part = format!("{}.part", output);
+    let result = get_writer(&part).and_then(|mut writer| {
+        f(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => Ok(fs::rename(&part, output)?),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
 pub fn get_content(input: &str) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();